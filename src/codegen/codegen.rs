@@ -1,6 +1,10 @@
 #![allow(dead_code)]
 
+use std::collections::HashMap;
+use std::io::{self, Write};
+
 use crate::parser::ast;
+use crate::sema::types;
 
 struct RspTracker {
     current: usize,
@@ -11,11 +15,20 @@ impl RspTracker {
         Self { current: 0 }
     }
 
+    // reserves `size` bytes below the ones already handed out, aligned to their size,
+    // and returns the new slot's offset from rbp
     pub fn next_offset(&mut self, size: usize) -> usize {
-        const ALLIGNMENT: usize = 8;
-        let old = self.current;
-        self.current += ((size + ALLIGNMENT - 1) + 8) & !(ALLIGNMENT - 1);
-        old
+        self.current = (self.current + size + size - 1) & !(size - 1);
+        self.current
+    }
+
+    // the stack frame size, keeps rsp 16-byte aligned for calls
+    pub fn frame_size(&self) -> usize {
+        (self.current + 15) & !15
+    }
+
+    pub fn reset(&mut self) {
+        self.current = 0;
     }
 }
 
@@ -28,8 +41,92 @@ pub struct State {
     text_buffer: Vec<String>,
     data_buffer: Vec<String>,
     bss_buffer: Vec<String>,
+    rodata_buffer: Vec<String>,
 
     rsp_tracker: RspTracker,
+    // local variables of the current function, name -> (offset from rbp, type)
+    scopes: Vec<HashMap<String, (usize, ast::Type)>>,
+    globals: HashMap<String, ast::Type>,
+    // return types of the declared functions
+    functions: HashMap<String, ast::Type>,
+    // index of the current function's `sub rsp` line, patched once the frame size is known
+    frame_line: usize,
+    // emit a `_start` that calls main and exits, for linking without libc
+    start_stub: bool,
+    // string literals, a literal's index is its label number in .rodata
+    string_pool: Vec<Vec<u8>>,
+    // 8-byte values pushed below the frame, to keep calls 16-byte aligned
+    stack_depth: usize,
+    label_counter: usize,
+    // the loops around the current expression, innermost last
+    loops: Vec<LoopLabels>,
+    // the epilogue of the current function, every `return` jumps there
+    return_label: String,
+    // for each switch arm around the current expression, the body of the next arm
+    // and the stack depth to restore before `fallthrough` jumps there
+    switch_arms: Vec<(Option<String>, usize)>,
+}
+
+// where `continue` and `break` jump to, and the stack depth to restore first
+struct LoopLabels {
+    // the `'name` the loop was labeled with
+    name: Option<String>,
+    continue_label: String,
+    break_label: String,
+    stack_depth: usize,
+}
+
+// System V argument registers, in order
+const ARG_REGISTERS: [&str; 6] = ["rdi", "rsi", "rdx", "rcx", "r8", "r9"];
+
+// the operands of a NASM `db` for `bytes`, NUL terminated.
+// printable runs are quoted, everything else is written as a number
+fn db_operands(bytes: &[u8]) -> String {
+    let mut operands: Vec<String> = Vec::new();
+    let mut run = String::new();
+    for &byte in bytes {
+        if (0x20..=0x7e).contains(&byte) && byte != b'"' && byte != b'\\' {
+            run.push(byte as char);
+            continue;
+        }
+        if !run.is_empty() {
+            operands.push(format!("\"{}\"", std::mem::take(&mut run)));
+        }
+        operands.push(byte.to_string());
+    }
+    if !run.is_empty() {
+        operands.push(format!("\"{}\"", run));
+    }
+    operands.push("0".to_string());
+    operands.join(", ")
+}
+
+// the part of rax that holds a value of `size` bytes
+fn rax_part(size: usize) -> &'static str {
+    match size {
+        1 => "al",
+        2 => "ax",
+        4 => "eax",
+        _ => "rax",
+    }
+}
+
+fn size_keyword(size: usize) -> &'static str {
+    match size {
+        1 => "byte",
+        2 => "word",
+        4 => "dword",
+        _ => "qword",
+    }
+}
+
+// a jump table pays off from four cases on, as long as at most half of its entries are holes
+fn is_dense(targets: &[(i64, &str)]) -> bool {
+    let Some((first, last)) = targets.first().zip(targets.last()) else {
+        return false;
+    };
+    let range = last.0 as i128 - first.0 as i128 + 1;
+    targets.len() >= 4 && range <= 2 * targets.len() as i128
 }
 
 impl State {
@@ -40,40 +137,97 @@ impl State {
             text_buffer: Vec::new(),
             data_buffer: Vec::new(),
             bss_buffer: Vec::new(),
+            rodata_buffer: Vec::new(),
             rsp_tracker: RspTracker::new(),
+            scopes: Vec::new(),
+            globals: HashMap::new(),
+            functions: HashMap::new(),
+            frame_line: 0,
+            start_stub: false,
+            string_pool: Vec::new(),
+            stack_depth: 0,
+            label_counter: 0,
+            loops: Vec::new(),
+            return_label: String::new(),
+            switch_arms: Vec::new(),
         }
     }
 
-    pub fn generate(&mut self) {
+    pub fn with_start_stub(mut self, start_stub: bool) -> Self {
+        self.start_stub = start_stub;
+        self
+    }
+
+    // generates the whole program and writes the assembly to `out`
+    pub fn generate<W: Write + ?Sized>(&mut self, out: &mut W) -> io::Result<()> {
         let program = std::mem::take(&mut self.program);
+        if self.start_stub {
+            self.gen_start_stub();
+        }
+        // functions can be called before they are declared
+        for decl in program.iter() {
+            if let ast::DeclType::FunctionDecl { name, func_type, .. } = decl {
+                self.functions.insert(name.clone(), (**func_type).clone());
+            }
+        }
         for decl in program.iter() {
-            self.gen_decl(&decl);
+            self.gen_global(decl);
         }
         self.program = program;
-        // DEBUG:
-        println!("-------------------------------");
+
         for line in self.extern_buffer.iter() {
-            println!("{}", line);
+            writeln!(out, "{}", line)?;
         }
-        println!("\nsection .text");
-        for line in self.text_buffer.iter() {
-            println!("{}", line);
-        }
-        println!("\nsection .data");
-        for line in self.data_buffer.iter() {
-            println!("{}", line);
+        let sections = [
+            ("text", &self.text_buffer),
+            ("data", &self.data_buffer),
+            ("bss", &self.bss_buffer),
+            ("rodata", &self.rodata_buffer),
+        ];
+        for (name, buffer) in sections {
+            writeln!(out, "\nsection .{}", name)?;
+            for line in buffer.iter() {
+                writeln!(out, "{}", line)?;
+            }
         }
-        println!("-------------------------------");
+        out.flush()
     }
 
     fn gen_label(&mut self, label: &str) {
         self.text_buffer.push(format!("{}:", label));
     }
 
+    // a fresh label that is unique in the whole program
+    fn new_label(&mut self, prefix: &str) -> String {
+        self.label_counter += 1;
+        format!(".{}_{}", prefix, self.label_counter)
+    }
+
     fn gen_inst(&mut self, inst: &str, args: &str) {
         self.text_buffer.push(format!("    {} {}", inst, args));
     }
 
+    fn gen_push(&mut self, reg: &str) {
+        self.stack_depth += 1;
+        self.gen_inst("push", reg);
+    }
+
+    fn gen_pop(&mut self, reg: &str) {
+        self.stack_depth -= 1;
+        self.gen_inst("pop", reg);
+    }
+
+    // interns a string literal and returns its label
+    fn gen_string(&mut self, bytes: &[u8]) -> String {
+        if let Some(id) = self.string_pool.iter().position(|s| s == bytes) {
+            return format!("str_{}", id);
+        }
+        let label = format!("str_{}", self.string_pool.len());
+        self.gen_rodata(&label, &format!("db {}", db_operands(bytes)));
+        self.string_pool.push(bytes.to_vec());
+        label
+    }
+
     fn gen_extern(&mut self, name: &str) {
         self.extern_buffer.push(format!("extern {}", name));
     }
@@ -82,84 +236,689 @@ impl State {
         self.data_buffer.push(format!("{}: {}", label, value));
     }
 
+    fn gen_bss(&mut self, label: &str, value: &str) {
+        self.bss_buffer.push(format!("{}: {}", label, value));
+    }
+
+    fn gen_rodata(&mut self, label: &str, value: &str) {
+        self.rodata_buffer.push(format!("{}: {}", label, value));
+    }
+
+    fn gen_global_directive(&mut self, name: &str) {
+        self.extern_buffer.push(format!("global {}", name));
+    }
+
+    fn gen_start_stub(&mut self) {
+        self.gen_global_directive("_start");
+        self.gen_label("_start");
+        self.gen_inst("xor", "rbp, rbp");
+        self.gen_inst("call", "main");
+        self.gen_inst("mov", "rdi, rax");
+        self.gen_inst("mov", "rax, 60"); // exit
+        self.gen_inst("syscall", "");
+    }
+
     fn gen_func_prologue(&mut self, name: &str) {
         self.gen_label(name);
         self.gen_inst("push", "rbp");
         self.gen_inst("mov", "rbp, rsp");
+        self.frame_line = self.text_buffer.len();
+        self.gen_inst("sub", "rsp, 0");
     }
 
     fn gen_func_epilogue(&mut self) {
+        let frame_size = self.rsp_tracker.frame_size();
+        if frame_size == 0 {
+            self.text_buffer.remove(self.frame_line);
+        } else {
+            self.text_buffer[self.frame_line] = format!("    sub rsp, {}", frame_size);
+        }
         self.gen_inst("mov", "rsp, rbp");
         self.gen_inst("pop", "rbp");
         self.gen_inst("ret", "");
     }
 
-    fn gen_decl(&mut self, decl: &ast::DeclType) {
+    fn lookup_local(&self, name: &str) -> Option<usize> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name).map(|(offset, _)| *offset))
+    }
+
+    fn declare_local(&mut self, name: &str, offset: usize, var_type: &ast::Type) {
+        self.scopes.last_mut().unwrap().insert(name.to_string(), (offset, var_type.clone()));
+    }
+
+    // the type the value of `expr` is kept as, an 8-byte int unless known otherwise
+    fn value_type(&self, expr: &ast::Expr) -> ast::Type {
+        types::type_of(self, expr).unwrap_or(ast::Type::Int)
+    }
+
+    // loads the value of type `ty` at `address` into rax, sign or zero extended to 64 bits
+    fn gen_load(&mut self, ty: &ast::Type, address: &str) {
+        let size = ty.size();
+        let memory = format!("{} [{}]", size_keyword(size), address);
+        match (size, ty.is_unsigned()) {
+            (8, _) => self.gen_inst("mov", &format!("rax, [{}]", address)),
+            (4, true) => self.gen_inst("mov", &format!("eax, {}", memory)),
+            (4, false) => self.gen_inst("movsxd", &format!("rax, {}", memory)),
+            (_, true) => self.gen_inst("movzx", &format!("eax, {}", memory)),
+            (_, false) => self.gen_inst("movsx", &format!("rax, {}", memory)),
+        }
+    }
+
+    // stores the low bytes of rax that make up a value of type `ty` at `address`
+    fn gen_store(&mut self, ty: &ast::Type, address: &str) {
+        self.gen_inst("mov", &format!("[{}], {}", address, rax_part(ty.size())));
+    }
+
+    // truncates rax to a value of type `ty` and extends it back to 64 bits,
+    // the way it would read back after a store
+    fn gen_extend(&mut self, ty: &ast::Type) {
+        let size = ty.size();
+        match (size, ty.is_unsigned()) {
+            (8, _) => {}
+            (4, true) => self.gen_inst("mov", "eax, eax"),
+            (4, false) => self.gen_inst("movsxd", "rax, eax"),
+            (_, true) => self.gen_inst("movzx", &format!("eax, {}", rax_part(size))),
+            (_, false) => self.gen_inst("movsx", &format!("rax, {}", rax_part(size))),
+        }
+    }
+
+    // puts the address of the lvalue `expr` in rax
+    fn gen_address(&mut self, expr: &ast::Expr) {
+        match expr {
+            ast::Expr::Identifier { name, .. } | ast::Expr::Variable(name) => {
+                if let Some(offset) = self.lookup_local(name) {
+                    self.gen_inst("lea", &format!("rax, [rbp - {}]", offset));
+                } else {
+                    self.gen_inst("lea", &format!("rax, [rel {}]", name));
+                }
+            }
+            ast::Expr::UnaryOp { op: ast::UnaryOperator::Deref, expr, .. } => {
+                self.gen_expr(expr);
+            }
+            // the parser only lets lvalues through
+            _ => unreachable!(),
+        }
+    }
+
+    fn gen_global(&mut self, decl: &ast::DeclType) {
         match decl {
              ast::DeclType::FunctionDecl { name, params, body, func_type } => {
-                 self.gen_function(&name, &params, &func_type, &body);
+                 self.gen_function(name, params, func_type, body);
              }
-             ast::DeclType::VariableDecl { name, var_type, init, mutability } => {
-                 self.gen_var_decl(&name, &var_type, &init, &mutability);
+             ast::DeclType::VariableDecl { name, var_type, init, .. } => {
+                 self.gen_global_var(name, var_type, init);
              }
-             _ => todo!("Code generation for other declaration types not implemented yet"),
+             // variants were replaced by their values
+             ast::DeclType::EnumDecl { .. } => {}
+             ast::DeclType::SideEffect(_) => unreachable!("the parser rejects expressions outside of functions"),
+        }
+    }
+
+    fn gen_decl(&mut self, decl: &ast::DeclType) {
+        match decl {
+             ast::DeclType::VariableDecl { name, var_type, init, mutability, .. } => {
+                 self.gen_var_decl(name, var_type, init, mutability);
+             }
+             ast::DeclType::SideEffect(expr) => {
+                 self.gen_expr(expr);
+             }
+             ast::DeclType::FunctionDecl { .. } => unreachable!("the parser rejects nested functions"),
+             ast::DeclType::EnumDecl { .. } => unreachable!("enums are only declared at the top level"),
+        }
+    }
+
+    fn gen_global_var(&mut self, name: &str, var_type: &ast::Type, init: &Option<Box<ast::Expr>>) {
+        self.globals.insert(name.to_string(), var_type.clone());
+        self.gen_global_directive(name);
+        // globals are laid out by the assembler, sema only lets constant initializers through
+        let (reserve, define) = match var_type.size() {
+            1 => ("resb", "db"),
+            2 => ("resw", "dw"),
+            4 => ("resd", "dd"),
+            _ => ("resq", "dq"),
+        };
+        match init.as_deref() {
+            None => self.gen_bss(name, &format!("{} 1", reserve)),
+            // the global points at the literal in .rodata
+            Some(ast::Expr::StringLiteral(bytes)) => {
+                let label = self.gen_string(bytes);
+                self.gen_data(name, &format!("dq {}", label));
+            }
+            Some(init) => {
+                let value = init.const_value().expect("global initializers are constant");
+                self.gen_data(name, &format!("{} {}", define, value));
+            }
         }
     }
-    
-    fn gen_var_decl(&mut self, name: &str, var_type: &ast::Type, init: &Option<Box<ast::Expr>>, mutability: &ast::Mutability) {
-        let offset = self.rsp_tracker.next_offset(32);
-        self.gen_inst("mov", &format!("[rbp - {}], 0", offset));
 
+    fn gen_var_decl(&mut self, name: &str, var_type: &ast::Type, init: &Option<Box<ast::Expr>>, _mutability: &ast::Mutability) {
+        let offset = self.rsp_tracker.next_offset(var_type.size());
+        match init {
+            Some(init) => {
+                self.gen_expr(init);
+                self.gen_store(var_type, &format!("rbp - {}", offset));
+            }
+            None => self.gen_inst("mov", &format!("{} [rbp - {}], 0", size_keyword(var_type.size()), offset)),
+        }
+        // declared after the initializer, so `var x = x;` reads the outer `x`
+        self.declare_local(name, offset, var_type);
     }
 
-    fn gen_function(&mut self, name: &str, params: &Vec<ast::Parameter>, return_type: &ast::Type, body: &Option<Box<ast::Expr>>) {
+    fn gen_function(&mut self, name: &str, params: &[ast::Parameter], return_type: &ast::Type, body: &Option<Box<ast::Expr>>) {
         if body.is_none() {
             self.gen_extern(name);
             return;
         }
 
+        self.gen_global_directive(name);
+
+        self.rsp_tracker.reset();
+        self.stack_depth = 0;
+        self.scopes.push(HashMap::new());
         self.gen_func_prologue(name);
+        // spill the arguments so parameters behave like locals, the ones
+        // past the sixth are above the return address
+        for (i, param) in params.iter().enumerate() {
+            let ptype = param.ptype();
+            let offset = self.rsp_tracker.next_offset(ptype.size());
+            match ARG_REGISTERS.get(i) {
+                Some(reg) if ptype.size() == 8 => self.gen_inst("mov", &format!("[rbp - {}], {}", offset, reg)),
+                Some(reg) => {
+                    self.gen_inst("mov", &format!("rax, {}", reg));
+                    self.gen_store(ptype, &format!("rbp - {}", offset));
+                }
+                None => {
+                    let slot = 16 + 8 * (i - ARG_REGISTERS.len());
+                    self.gen_inst("mov", &format!("rax, [rbp + {}]", slot));
+                    self.gen_store(ptype, &format!("rbp - {}", offset));
+                }
+            }
+            if let Some(pname) = param.name() {
+                self.declare_local(pname, offset, param.ptype());
+            }
+        }
+        self.return_label = self.new_label("return");
         let body = body.as_ref().unwrap();
         self.gen_expr(body);
+        let return_label = self.return_label.clone();
+        self.gen_label(&return_label);
+        if name == "main" && *return_type == ast::Type::Void {
+            // falling off the end of main exits with 0, like C
+            self.gen_inst("xor", "eax, eax");
+        } else {
+            self.gen_extend(return_type);
+        }
         self.gen_func_epilogue();
+        self.scopes.pop();
     }
 
     fn gen_expr(&mut self, expr: &ast::Expr) {
-        match &*expr {
-            ast::Expr::IntegerLiteral(value) => {
+        match expr {
+            ast::Expr::IntegerLiteral { value, .. } => {
                 self.gen_inst("mov", &format!("rax, {}", value));
             }
+            ast::Expr::BinaryOp { lhs, op: op @ (ast::BinaryOperator::And | ast::BinaryOperator::Or), rhs } => {
+                // short-circuits: the rhs only runs if the lhs did not decide the result
+                let end = self.new_label("logic_end");
+                self.gen_expr(lhs);
+                self.gen_inst("cmp", "rax, 0");
+                self.gen_inst("setne", "al");
+                self.gen_inst("movzx", "eax, al");
+                let jump = if *op == ast::BinaryOperator::And { "je" } else { "jne" };
+                self.gen_inst(jump, &end);
+                self.gen_expr(rhs);
+                self.gen_inst("cmp", "rax, 0");
+                self.gen_inst("setne", "al");
+                self.gen_inst("movzx", "eax, al");
+                self.gen_label(&end);
+            }
+            ast::Expr::BinaryOp { lhs, op: ast::BinaryOperator::Assign, rhs } => {
+                self.gen_expr(rhs);
+                self.gen_push("rax");
+                self.gen_address(lhs);
+                self.gen_inst("mov", "r11, rax");
+                self.gen_pop("rax");
+                let ty = self.value_type(lhs);
+                self.gen_store(&ty, "r11");
+                self.gen_extend(&ty);
+            }
             ast::Expr::BinaryOp { lhs, op, rhs } => {
                 self.gen_expr(lhs);
-                self.gen_inst("push", "rax");
+                self.gen_push("rax");
                 self.gen_expr(rhs);
-                self.gen_inst("pop", "rbx");
+                self.gen_pop("r11");
+                let ty = self.value_type(lhs);
+                self.gen_binary_op(op, &ty);
+            }
+            ast::Expr::CompoundAssign { op, target, value } => {
+                // the target's address is computed once, `*f() += 1` calls f once
+                let ty = self.value_type(target);
+                self.gen_address(target);
+                self.gen_push("rax");
+                self.gen_load(&ty, "rax");
+                self.gen_push("rax");
+                self.gen_expr(value);
+                self.gen_pop("r11");
+                self.gen_binary_op(op, &ty);
+                self.gen_pop("r11");
+                self.gen_store(&ty, "r11");
+            }
+            ast::Expr::UnaryOp { op: ast::UnaryOperator::AddrOf, expr, .. } => {
+                self.gen_address(expr);
+            }
+            ast::Expr::UnaryOp { op, expr, .. } => {
+                self.gen_expr(expr);
                 match op {
-                    ast::BinaryOperator::Add => {
-                        self.gen_inst("add", "rax, rbx");
+                    ast::UnaryOperator::Neg => {
+                        self.gen_inst("neg", "rax");
+                        let ty = self.value_type(expr);
+                        self.gen_extend(&ty);
                     }
-                    ast::BinaryOperator::Sub => {
-                        self.gen_inst("sub", "rbx, rax");
-                        self.gen_inst("mov", "rax, rbx");
+                    ast::UnaryOperator::BitNot => {
+                        self.gen_inst("not", "rax");
+                        let ty = self.value_type(expr);
+                        self.gen_extend(&ty);
                     }
-                    ast::BinaryOperator::Mul => {
-                        self.gen_inst("imul", "rax, rbx");
+                    ast::UnaryOperator::Not => {
+                        self.gen_inst("cmp", "rax, 0");
+                        self.gen_inst("sete", "al");
+                        self.gen_inst("movzx", "eax, al");
                     }
-                    ast::BinaryOperator::Div => {
-                        self.gen_inst("xor", "rdx, rdx"); // Clear rdx before div
-                        self.gen_inst("mov", "rcx, rax"); // Move divisor to rcx
-                        self.gen_inst("mov", "rax, rbx"); // Move dividend to rax
-                        self.gen_inst("div", "rcx");       // rax = rax / rcx
+                    ast::UnaryOperator::Deref => {
+                        let ty = match types::type_of(self, expr) {
+                            Some(ast::Type::PointerType { to } | ast::Type::RefType { to, .. }) => *to,
+                            _ => ast::Type::Int,
+                        };
+                        self.gen_load(&ty, "rax");
                     }
-                    _ => todo!("Code generation for other binary operators not implemented yet"),
+                    // handled above, the operand is not evaluated for its value
+                    ast::UnaryOperator::AddrOf => unreachable!(),
+                }
+            }
+            ast::Expr::Identifier { name, .. } | ast::Expr::Variable(name) => {
+                let ty = self.value_type(expr);
+                if let Some(offset) = self.lookup_local(name) {
+                    self.gen_load(&ty, &format!("rbp - {}", offset));
+                } else if self.globals.contains_key(name) {
+                    self.gen_load(&ty, &format!("rel {}", name));
+                } else {
+                    // a function, the value is its address
+                    self.gen_inst("lea", &format!("rax, [rel {}]", name));
+                }
+            }
+            ast::Expr::CharLiteral(byte) => {
+                self.gen_inst("xor", "eax, eax");
+                self.gen_inst("mov", &format!("al, {}", byte));
+            }
+            ast::Expr::StringLiteral(bytes) => {
+                let label = self.gen_string(bytes);
+                self.gen_inst("lea", &format!("rax, [rel {}]", label));
+            }
+            ast::Expr::Call { func, args } => {
+                self.gen_call(func, args);
+                // the upper bits of a narrow return value are not guaranteed
+                let ty = self.value_type(expr);
+                self.gen_extend(&ty);
+            }
+            ast::Expr::If { cond, then_branch, else_branch } => {
+                // the arm that runs leaves its value in rax
+                let else_label = self.new_label("if_else");
+                let end = self.new_label("if_end");
+                self.gen_expr(cond);
+                self.gen_inst("cmp", "rax, 0");
+                self.gen_inst("je", &else_label);
+                self.gen_expr(then_branch);
+                self.gen_inst("jmp", &end);
+                self.gen_label(&else_label);
+                if let Some(else_branch) = else_branch {
+                    self.gen_expr(else_branch);
+                }
+                self.gen_label(&end);
+            }
+            ast::Expr::While { label, cond, body } => {
+                let start = self.new_label("while_cond");
+                let end = self.new_label("while_end");
+                self.gen_label(&start);
+                self.gen_expr(cond);
+                self.gen_inst("cmp", "rax, 0");
+                self.gen_inst("je", &end);
+                self.gen_loop_body(label, body, &start, &end);
+                self.gen_inst("jmp", &start);
+                self.gen_label(&end);
+            }
+            ast::Expr::DoWhile { label, body, cond } => {
+                let start = self.new_label("do_body");
+                let cond_label = self.new_label("do_cond");
+                let end = self.new_label("do_end");
+                self.gen_label(&start);
+                self.gen_loop_body(label, body, &cond_label, &end);
+                self.gen_label(&cond_label);
+                self.gen_expr(cond);
+                self.gen_inst("cmp", "rax, 0");
+                self.gen_inst("jne", &start);
+                self.gen_label(&end);
+            }
+            ast::Expr::For { label, init, cond, step, body } => {
+                let start = self.new_label("for_cond");
+                let step_label = self.new_label("for_step");
+                let end = self.new_label("for_end");
+                self.scopes.push(HashMap::new());
+                if let Some(init) = init {
+                    self.gen_decl(init);
+                }
+                self.gen_label(&start);
+                if let Some(cond) = cond {
+                    self.gen_expr(cond);
+                    self.gen_inst("cmp", "rax, 0");
+                    self.gen_inst("je", &end);
                 }
+                self.gen_loop_body(label, body, &step_label, &end);
+                self.gen_label(&step_label);
+                if let Some(step) = step {
+                    self.gen_expr(step);
+                }
+                self.gen_inst("jmp", &start);
+                self.gen_label(&end);
+                self.scopes.pop();
+            }
+            ast::Expr::Break { label, .. } | ast::Expr::Continue { label, .. } => {
+                // sema rejects them outside of loops and with unknown labels
+                let target = match label {
+                    Some(label) => self.loops.iter().rev().find(|l| l.name.as_ref() == Some(&label.name)).unwrap(),
+                    None => self.loops.last().unwrap(),
+                };
+                let label = if matches!(expr, ast::Expr::Break { .. }) {
+                    target.break_label.clone()
+                } else {
+                    target.continue_label.clone()
+                };
+                // drop whatever the enclosing expression pushed since the loop started
+                let extra = self.stack_depth - target.stack_depth;
+                if extra > 0 {
+                    self.gen_inst("add", &format!("rsp, {}", extra * 8));
+                }
+                self.gen_inst("jmp", &label);
+            }
+            ast::Expr::Switch { scrutinee, cases, .. } => {
+                self.gen_switch(scrutinee, cases);
+            }
+            ast::Expr::Fallthrough { .. } => {
+                // sema rejects it without a following arm
+                let (next, stack_depth) = self.switch_arms.last().cloned().unwrap();
+                let extra = self.stack_depth - stack_depth;
+                if extra > 0 {
+                    self.gen_inst("add", &format!("rsp, {}", extra * 8));
+                }
+                self.gen_inst("jmp", &next.unwrap());
             }
-            ast::Expr::CompoundExpr { expressions } => {
+            ast::Expr::Return { value, .. } => {
+                if let Some(value) = value {
+                    self.gen_expr(value);
+                }
+                // the epilogue resets rsp, pushed temporaries need no cleanup
+                let return_label = self.return_label.clone();
+                self.gen_inst("jmp", &return_label);
+            }
+            ast::Expr::CompoundExpr { expressions, .. } => {
+                self.scopes.push(HashMap::new());
                 for decl in expressions.iter() {
                     self.gen_decl(decl);
                 }
+                self.scopes.pop();
+            }
+        }
+    }
+
+    fn gen_switch(&mut self, scrutinee: &ast::Expr, cases: &[ast::SwitchCase]) {
+        let labels: Vec<String> = cases.iter().map(|_| self.new_label("case")).collect();
+        let end = self.new_label("switch_end");
+        let default = match cases.iter().position(|case| case.is_default()) {
+            Some(i) => labels[i].clone(),
+            None => end.clone(),
+        };
+        // sema rejects duplicates, so every value has one target
+        let mut targets: Vec<(i64, &str)> = cases.iter()
+            .zip(labels.iter())
+            .flat_map(|(case, label)| case.values.iter().map(move |v| (v.value, label.as_str())))
+            .collect();
+        targets.sort_by_key(|(value, _)| *value);
+
+        self.gen_expr(scrutinee);
+        if is_dense(&targets) {
+            self.gen_jump_table(&targets, &default);
+        } else {
+            self.gen_compare_chain(&targets, &default);
+        }
+
+        // the arm that runs leaves its value in rax
+        for (i, case) in cases.iter().enumerate() {
+            self.gen_label(&labels[i]);
+            self.switch_arms.push((labels.get(i + 1).cloned(), self.stack_depth));
+            self.gen_expr(&case.body);
+            self.switch_arms.pop();
+            self.gen_inst("jmp", &end);
+        }
+        self.gen_label(&end);
+    }
+
+    // indexes a table of offsets with the scrutinee in rax, `targets` is sorted
+    fn gen_jump_table(&mut self, targets: &[(i64, &str)], default: &str) {
+        let min = targets[0].0;
+        let max = targets[targets.len() - 1].0;
+        let table = self.new_label("switch_table");
+        // rebase to the smallest case, values below it wrap around and fail the unsigned check
+        if min != 0 {
+            self.gen_inst("mov", &format!("r11, {}", min));
+            self.gen_inst("sub", "rax, r11");
+        }
+        self.gen_inst("cmp", &format!("rax, {}", max - min));
+        self.gen_inst("ja", default);
+        self.gen_inst("lea", &format!("r11, [rel {}]", table));
+        self.gen_inst("movsxd", "rax, dword [r11 + rax*4]");
+        self.gen_inst("add", "rax, r11");
+        self.gen_inst("jmp", "rax");
+        // entries are relative to the table, so it needs no relocations
+        self.gen_label(&table);
+        let mut targets = targets.iter().peekable();
+        for value in min..=max {
+            let target = match targets.next_if(|(v, _)| *v == value) {
+                Some((_, label)) => label,
+                None => default,
+            };
+            self.gen_inst("dd", &format!("{} - {}", target, table));
+        }
+    }
+
+    fn gen_compare_chain(&mut self, targets: &[(i64, &str)], default: &str) {
+        for (value, label) in targets.iter() {
+            // cmp only takes a sign-extended 32-bit immediate
+            if i32::try_from(*value).is_ok() {
+                self.gen_inst("cmp", &format!("rax, {}", value));
+            } else {
+                self.gen_inst("mov", &format!("r11, {}", value));
+                self.gen_inst("cmp", "rax, r11");
+            }
+            self.gen_inst("je", label);
+        }
+        self.gen_inst("jmp", default);
+    }
+
+    fn gen_loop_body(
+        &mut self,
+        label: &Option<ast::LoopLabel>,
+        body: &ast::Expr,
+        continue_label: &str,
+        break_label: &str,
+    ) {
+        self.loops.push(LoopLabels {
+            name: label.as_ref().map(|l| l.name.clone()),
+            continue_label: continue_label.to_string(),
+            break_label: break_label.to_string(),
+            stack_depth: self.stack_depth,
+        });
+        self.gen_expr(body);
+        self.loops.pop();
+    }
+
+    // applies `op` to the lhs in r11 and the rhs in rax, leaving the result in rax.
+    // `ty` is the type of the lhs, the operation is unsigned when it is
+    fn gen_binary_op(&mut self, op: &ast::BinaryOperator, ty: &ast::Type) {
+        let unsigned = ty.is_unsigned();
+        match op {
+            ast::BinaryOperator::Add => {
+                self.gen_inst("add", "rax, r11");
+            }
+            ast::BinaryOperator::Sub => {
+                self.gen_inst("sub", "r11, rax");
+                self.gen_inst("mov", "rax, r11");
+            }
+            ast::BinaryOperator::Mul => {
+                self.gen_inst("imul", "rax, r11");
+            }
+            ast::BinaryOperator::Div | ast::BinaryOperator::Mod => {
+                self.gen_inst("mov", "rcx, rax"); // Move divisor to rcx
+                self.gen_inst("mov", "rax, r11"); // Move dividend to rax
+                if unsigned {
+                    self.gen_inst("xor", "edx, edx"); // Zero extend rax into rdx
+                    self.gen_inst("div", "rcx");
+                } else {
+                    self.gen_inst("cqo", "");          // Sign extend rax into rdx
+                    self.gen_inst("idiv", "rcx");      // rax = rdx:rax / rcx, rdx = remainder
+                }
+                if *op == ast::BinaryOperator::Mod {
+                    self.gen_inst("mov", "rax, rdx");
+                }
+            }
+            ast::BinaryOperator::BitAnd => {
+                self.gen_inst("and", "rax, r11");
+            }
+            ast::BinaryOperator::BitOr => {
+                self.gen_inst("or", "rax, r11");
+            }
+            ast::BinaryOperator::Xor => {
+                self.gen_inst("xor", "rax, r11");
+            }
+            ast::BinaryOperator::Shl | ast::BinaryOperator::Shr => {
+                self.gen_inst("mov", "rcx, rax");
+                self.gen_inst("mov", "rax, r11");
+                let inst = match op {
+                    ast::BinaryOperator::Shl => "shl",
+                    _ if unsigned => "shr",
+                    _ => "sar",
+                };
+                self.gen_inst(inst, "rax, cl");
+            }
+            ast::BinaryOperator::Eq | ast::BinaryOperator::Neq
+            | ast::BinaryOperator::Lt | ast::BinaryOperator::Gt
+            | ast::BinaryOperator::Leq | ast::BinaryOperator::Geq => {
+                let set = match (op, unsigned) {
+                    (ast::BinaryOperator::Eq, _) => "sete",
+                    (ast::BinaryOperator::Neq, _) => "setne",
+                    (ast::BinaryOperator::Lt, false) => "setl",
+                    (ast::BinaryOperator::Gt, false) => "setg",
+                    (ast::BinaryOperator::Leq, false) => "setle",
+                    (ast::BinaryOperator::Geq, false) => "setge",
+                    (ast::BinaryOperator::Lt, true) => "setb",
+                    (ast::BinaryOperator::Gt, true) => "seta",
+                    (ast::BinaryOperator::Leq, true) => "setbe",
+                    _ => "setae",
+                };
+                self.gen_inst("cmp", "r11, rax");
+                self.gen_inst(set, "al");
+                self.gen_inst("movzx", "eax, al");
+                // the result is an int, there is nothing to truncate
+                return;
+            }
+            // gen_expr lowers these itself, with jumps around the rhs and with stores
+            ast::BinaryOperator::And | ast::BinaryOperator::Or | ast::BinaryOperator::Assign => {
+                unreachable!("`{:?}` is not computed from two values", op)
+            }
+        }
+        // arithmetic wraps around in the type of the lhs
+        self.gen_extend(ty);
+    }
+
+    fn gen_call(&mut self, func: &ast::Expr, args: &[ast::Expr]) {
+        // functions are called by name, anything else through a pointer
+        let direct = match func {
+            ast::Expr::Identifier { name, .. } if self.lookup_local(name).is_none() && !self.globals.contains_key(name) => {
+                Some(name.clone())
             }
-            _ => todo!("Code generation for other expression types not implemented yet"),
+            _ => None,
+        };
+        let base = self.stack_depth;
+        if direct.is_none() {
+            self.gen_expr(func);
+            self.gen_push("rax");
         }
+
+        // every argument is evaluated before any register is loaded,
+        // an argument's evaluation could clobber them otherwise
+        let mut depths = Vec::with_capacity(args.len());
+        for arg in args.iter() {
+            self.gen_expr(arg);
+            self.gen_push("rax");
+            depths.push(self.stack_depth);
+        }
+
+        if args.len() <= ARG_REGISTERS.len() {
+            for reg in ARG_REGISTERS[..args.len()].iter().rev() {
+                self.gen_pop(reg);
+            }
+            if direct.is_none() {
+                self.gen_pop("r11");
+            }
+            let misaligned = self.stack_depth % 2 == 1;
+            if misaligned {
+                self.gen_inst("sub", "rsp, 8");
+            }
+            self.gen_call_inst(&direct);
+            if misaligned {
+                self.gen_inst("add", "rsp, 8");
+            }
+            return;
+        }
+
+        // the stack arguments are copied below the evaluated ones in reverse,
+        // so the seventh ends up at [rsp] when the call is made
+        let on_stack = args.len() - ARG_REGISTERS.len();
+        if (self.stack_depth + on_stack) % 2 == 1 {
+            self.gen_inst("sub", "rsp, 8");
+            self.stack_depth += 1;
+        }
+        for depth in depths[ARG_REGISTERS.len()..].iter().rev() {
+            let operand = format!("qword [rsp + {}]", 8 * (self.stack_depth - depth));
+            self.gen_push(&operand);
+        }
+        for (reg, depth) in ARG_REGISTERS.iter().zip(depths.iter()) {
+            self.gen_inst("mov", &format!("{}, [rsp + {}]", reg, 8 * (self.stack_depth - depth)));
+        }
+        if direct.is_none() {
+            self.gen_inst("mov", &format!("r11, [rsp + {}]", 8 * (self.stack_depth - base - 1)));
+        }
+        self.gen_call_inst(&direct);
+        self.gen_inst("add", &format!("rsp, {}", 8 * (self.stack_depth - base)));
+        self.stack_depth = base;
+    }
+
+    fn gen_call_inst(&mut self, direct: &Option<String>) {
+        // variadic callees read the number of vector registers used from al
+        self.gen_inst("xor", "eax, eax");
+        match direct {
+            Some(name) => self.gen_inst("call", name),
+            None => self.gen_inst("call", "r11"),
+        }
+    }
+}
+
+impl types::Names for State {
+    fn variable_type(&self, name: &str) -> Option<ast::Type> {
+        self.scopes.iter().rev()
+            .find_map(|scope| scope.get(name).map(|(_, t)| t.clone()))
+            .or_else(|| self.globals.get(name).cloned())
+    }
+
+    fn return_type(&self, name: &str) -> Option<ast::Type> {
+        self.functions.get(name).cloned()
     }
 }
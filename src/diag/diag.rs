@@ -1,5 +1,7 @@
 #![allow(dead_code)]
 
+use std::fmt;
+
 use crate::parser::token::Span;
 use crate::parser::ast::{Type, Mutability};
 
@@ -8,6 +10,13 @@ pub enum Diag {
     // Lexical Errors
     InvalidCharacter(char, Span),
     UnterminatedString(Span),
+    InvalidEscape(String, Span),
+    UnterminatedChar(Span),
+    EmptyCharLiteral(Span),
+    MultiCharLiteral(Span),
+    NonAsciiCharLiteral(Span),
+    // points at the opening '/*'
+    UnterminatedComment(Span),
     InvalidNumberFormat(String, Span),
     // Syntax Errors
     UnexpectedToken(String, Span),
@@ -15,6 +24,8 @@ pub enum Diag {
     DeclarationMissingAName(Span),
     MissingIdentifier(Span),
     EarlyEOF(Span),
+    // the operand of `operation` does not name a memory location
+    NotAnLvalue(String, Span),
     // Semantic Errors
     UndefinedVariable {
         err_loc: Span,
@@ -27,17 +38,323 @@ pub enum Diag {
     },
     TypeMismatch {
         err_loc: Span,
-        expected: Type,
-        got: Type,
+        expected: Box<Type>,
+        got: Box<Type>,
     },
     ReferenceMutMismatch {
         err_loc: Span,
         expected: Mutability,
         got: Mutability,
     },
+    IntegerOutOfRange {
+        err_loc: Span,
+        literal: String,
+        ty: Type,
+    },
     InvalidOperation {
         err_loc: Span,
         operation: String,
         operand_type: Type,
     },
+    // `break` or `continue` with no loop around it
+    OutsideOfLoop {
+        err_loc: Span,
+        keyword: String,
+    },
+    // `break 'name` or `continue 'name` with no enclosing loop called 'name
+    UndeclaredLabel {
+        err_loc: Span,
+        label: String,
+    },
+    // a loop labeled with the name of a loop around it
+    ShadowedLabel {
+        err_loc: Span,
+        label: String,
+        prev_decl: Span,
+    },
+    // two arms of one switch match the same value, or there are two `default` arms.
+    // `case` is how the arm is written, like `case 3` or `default`
+    DuplicateCase {
+        err_loc: Span,
+        case: String,
+        prev_decl: Span,
+    },
+    // `fallthrough` outside of a switch arm, or in the last one
+    InvalidFallthrough {
+        err_loc: Span,
+    },
+    // a `fn` inside the body of another function
+    NestedFunction(Span),
+    // an expression statement at the top level of a file
+    ExpressionOutsideFunction(Span),
+    // a global whose initializer cannot be laid out by the assembler
+    NonConstantGlobal {
+        err_loc: Span,
+        var_name: String,
+    },
+    // the body of a function returning a value can end without one,
+    // `err_loc` is its closing '}'
+    MissingReturnValue {
+        err_loc: Span,
+        function: String,
+    },
+}
+
+impl Diag {
+    // where the error was found
+    pub fn span(&self) -> &Span {
+        match self {
+            Diag::InvalidCharacter(_, span)
+            | Diag::UnterminatedString(span)
+            | Diag::InvalidEscape(_, span)
+            | Diag::UnterminatedChar(span)
+            | Diag::EmptyCharLiteral(span)
+            | Diag::MultiCharLiteral(span)
+            | Diag::NonAsciiCharLiteral(span)
+            | Diag::UnterminatedComment(span)
+            | Diag::InvalidNumberFormat(_, span)
+            | Diag::UnexpectedToken(_, span)
+            | Diag::MissingToken(_, span)
+            | Diag::DeclarationMissingAName(span)
+            | Diag::MissingIdentifier(span)
+            | Diag::EarlyEOF(span)
+            | Diag::NotAnLvalue(_, span)
+            | Diag::NestedFunction(span)
+            | Diag::ExpressionOutsideFunction(span) => span,
+            Diag::UndefinedVariable { err_loc, .. }
+            | Diag::RedefinedVariable { err_loc, .. }
+            | Diag::TypeMismatch { err_loc, .. }
+            | Diag::ReferenceMutMismatch { err_loc, .. }
+            | Diag::IntegerOutOfRange { err_loc, .. }
+            | Diag::InvalidOperation { err_loc, .. }
+            | Diag::OutsideOfLoop { err_loc, .. }
+            | Diag::UndeclaredLabel { err_loc, .. }
+            | Diag::ShadowedLabel { err_loc, .. }
+            | Diag::DuplicateCase { err_loc, .. }
+            | Diag::InvalidFallthrough { err_loc }
+            | Diag::NonConstantGlobal { err_loc, .. }
+            | Diag::MissingReturnValue { err_loc, .. } => err_loc,
+        }
+    }
+}
+
+impl Diag {
+    // the stable code the error is reported under
+    pub fn code(&self) -> &'static str {
+        match self {
+            Diag::InvalidCharacter(..) => "E0001",
+            Diag::UnterminatedString(..) => "E0002",
+            Diag::InvalidEscape(..) => "E0003",
+            Diag::UnterminatedChar(..) => "E0004",
+            Diag::EmptyCharLiteral(..) => "E0005",
+            Diag::MultiCharLiteral(..) => "E0006",
+            Diag::NonAsciiCharLiteral(..) => "E0007",
+            Diag::UnterminatedComment(..) => "E0008",
+            Diag::InvalidNumberFormat(..) => "E0009",
+            Diag::UnexpectedToken(..) => "E0010",
+            Diag::MissingToken(..) => "E0011",
+            Diag::DeclarationMissingAName(..) => "E0012",
+            Diag::MissingIdentifier(..) => "E0013",
+            Diag::EarlyEOF(..) => "E0014",
+            Diag::NotAnLvalue(..) => "E0015",
+            Diag::UndefinedVariable { .. } => "E0016",
+            Diag::RedefinedVariable { .. } => "E0017",
+            Diag::TypeMismatch { .. } => "E0018",
+            Diag::ReferenceMutMismatch { .. } => "E0019",
+            Diag::IntegerOutOfRange { .. } => "E0020",
+            Diag::InvalidOperation { .. } => "E0021",
+            Diag::OutsideOfLoop { .. } => "E0022",
+            Diag::UndeclaredLabel { .. } => "E0023",
+            Diag::ShadowedLabel { .. } => "E0024",
+            Diag::DuplicateCase { .. } => "E0025",
+            Diag::InvalidFallthrough { .. } => "E0026",
+            Diag::NestedFunction(..) => "E0027",
+            Diag::ExpressionOutsideFunction(..) => "E0028",
+            Diag::NonConstantGlobal { .. } => "E0029",
+            Diag::MissingReturnValue { .. } => "E0030",
+        }
+    }
+
+    // other places worth pointing at, with what they are
+    pub fn labels(&self) -> Vec<(&Span, String)> {
+        match self {
+            Diag::RedefinedVariable { var_name, prev_decl, .. } => {
+                vec![(prev_decl, format!("`{}` was first declared here", var_name))]
+            }
+            Diag::ShadowedLabel { label, prev_decl, .. } => {
+                vec![(prev_decl, format!("`'{}` is already the label of this loop", label))]
+            }
+            Diag::DuplicateCase { case, prev_decl, .. } => {
+                vec![(prev_decl, format!("`{}` first appears here", case))]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for Diag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Diag::InvalidCharacter(ch, _) => write!(f, "invalid character {:?}", ch),
+            Diag::UnterminatedString(_) => write!(f, "unterminated string literal"),
+            Diag::InvalidEscape(escape, _) => write!(f, "invalid escape sequence `{}`", escape),
+            Diag::UnterminatedChar(_) => write!(f, "unterminated character literal"),
+            Diag::EmptyCharLiteral(_) => write!(f, "empty character literal"),
+            Diag::MultiCharLiteral(_) => write!(f, "character literal holds more than one character"),
+            Diag::NonAsciiCharLiteral(_) => write!(f, "non-ASCII character literal"),
+            Diag::UnterminatedComment(_) => write!(f, "unterminated block comment"),
+            Diag::InvalidNumberFormat(text, _) => write!(f, "invalid number `{}`", text),
+            Diag::UnexpectedToken(token, _) => write!(f, "unexpected `{}`", token),
+            Diag::MissingToken(token, _) => write!(f, "expected `{}`", token),
+            Diag::DeclarationMissingAName(_) => write!(f, "declaration is missing a name"),
+            Diag::MissingIdentifier(_) => write!(f, "expected an identifier"),
+            Diag::EarlyEOF(_) => write!(f, "unexpected end of file"),
+            Diag::NotAnLvalue(operation, _) => write!(f, "the operand of `{}` is not assignable", operation),
+            Diag::UndefinedVariable { var_name, .. } => write!(f, "`{}` is not defined", var_name),
+            Diag::RedefinedVariable { var_name, .. } => write!(f, "`{}` is defined twice", var_name),
+            Diag::TypeMismatch { expected, got, .. } => {
+                write!(f, "mismatched types, expected `{}` but found `{}`", expected, got)
+            }
+            Diag::ReferenceMutMismatch { expected, got, .. } => {
+                let article = |m: &Mutability| if *m == Mutability::Immutable { "an" } else { "a" };
+                write!(f, "expected {} {} reference but found {} {} one", article(expected), expected, article(got), got)
+            }
+            Diag::IntegerOutOfRange { literal, ty, .. } => {
+                write!(f, "integer literal `{}` does not fit in `{}`", literal, ty)
+            }
+            Diag::InvalidOperation { operation, operand_type, .. } => {
+                write!(f, "`{}` cannot be applied to `{}`", operation, operand_type)
+            }
+            Diag::OutsideOfLoop { keyword, .. } => write!(f, "`{}` outside of a loop", keyword),
+            Diag::UndeclaredLabel { label, .. } => write!(f, "use of undeclared label `'{}`", label),
+            Diag::ShadowedLabel { label, .. } => {
+                write!(f, "label `'{}` shadows a label of an enclosing loop", label)
+            }
+            Diag::DuplicateCase { case, .. } => write!(f, "duplicate `{}` in switch", case),
+            Diag::InvalidFallthrough { .. } => write!(f, "`fallthrough` has no following switch arm"),
+            Diag::NestedFunction(_) => write!(f, "functions cannot be declared inside other functions"),
+            Diag::ExpressionOutsideFunction(_) => write!(f, "expressions are only allowed inside functions"),
+            Diag::NonConstantGlobal { var_name, .. } => {
+                write!(f, "the initializer of global `{}` is not a constant", var_name)
+            }
+            Diag::MissingReturnValue { function, .. } => {
+                write!(f, "`{}` can reach the end of its body without returning a value", function)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        }
+    }
+}
+
+// warnings that can be silenced with `-A <name>`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lint {
+    UnusedVariables,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 1] = [Lint::UnusedVariables];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Lint::UnusedVariables => "unused_variables",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Lint::ALL.into_iter().find(|lint| lint.name() == name)
+    }
+}
+
+// a secondary span and what it points out
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+// what gets reported, errors from `Diag` are converted into one of these
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: Option<&'static str>,
+    // the lint a warning belongs to
+    pub lint: Option<Lint>,
+    pub message: String,
+    pub span: Span,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: String, span: Span) -> Self {
+        Diagnostic {
+            severity,
+            code: None,
+            lint: None,
+            message,
+            span,
+            labels: Vec::new(),
+            notes: Vec::new(),
+            help: Vec::new(),
+        }
+    }
+
+    pub fn error(message: String, span: Span) -> Self {
+        Diagnostic::new(Severity::Error, message, span)
+    }
+
+    pub fn warning(lint: Lint, message: String, span: Span) -> Self {
+        Diagnostic { lint: Some(lint), ..Diagnostic::new(Severity::Warning, message, span) }
+    }
+
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn with_label(mut self, span: Span, message: String) -> Self {
+        self.labels.push(Label { span, message });
+        self
+    }
+
+    pub fn with_note(mut self, note: String) -> Self {
+        self.notes.push(note);
+        self
+    }
+
+    pub fn with_help(mut self, help: String) -> Self {
+        self.help.push(help);
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl From<Diag> for Diagnostic {
+    fn from(diag: Diag) -> Self {
+        let mut diagnostic = Diagnostic::error(diag.to_string(), diag.span().clone()).with_code(diag.code());
+        for (span, message) in diag.labels() {
+            diagnostic = diagnostic.with_label(span.clone(), message);
+        }
+        diagnostic
+    }
 }
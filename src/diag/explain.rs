@@ -0,0 +1,525 @@
+// the extended explanation printed by `cx --explain <code>`, one per `Diag::code`
+const EXPLANATIONS: [(&str, &str); 30] = [
+    ("E0001", "\
+A character that has no meaning in Cx appeared outside of a string, character
+literal or comment.
+
+Erroneous code example:
+
+    fn main() -> int {
+        1 @ 2
+    }
+
+Remove the character, or replace it with the operator that was meant:
+
+    fn main() -> int {
+        1 + 2
+    }
+"),
+    ("E0002", "\
+A string literal was not closed before the end of its line. Strings may not
+span several lines.
+
+Erroneous code example:
+
+    var greeting: *char = \"hello;
+
+Close the string on the line it starts on, use `\\n` for line breaks:
+
+    var greeting: *char = \"hello\\n\";
+"),
+    ("E0003", "\
+A string or character literal contains a `\\` that does not start a known
+escape sequence.
+
+Erroneous code example:
+
+    var path: *char = \"C:\\temp\\q\";
+
+The escapes are `\\n`, `\\t`, `\\r`, `\\0`, `\\\\`, `\\\"`, `\\'` and `\\xNN` with two
+hex digits. Write a backslash itself as `\\\\`:
+
+    var path: *char = \"C:\\\\temp\\\\q\";
+"),
+    ("E0004", "\
+A character literal was not closed by a `'`. A `'` followed by a name and
+nothing else is a loop label, so this error shows up for the other
+characters.
+
+Erroneous code example:
+
+    var newline: char = '\\n;
+
+Close the literal:
+
+    var newline: char = '\\n';
+"),
+    ("E0005", "\
+A character literal holds no character.
+
+Erroneous code example:
+
+    var c: char = '';
+
+A character literal must hold exactly one character, use `'\\0'` for a zero
+byte:
+
+    var c: char = '\\0';
+"),
+    ("E0006", "\
+A character literal holds more than one character.
+
+Erroneous code example:
+
+    var c: char = 'ab';
+
+Use a string literal for more than one character:
+
+    var s: *char = \"ab\";
+"),
+    ("E0007", "\
+A character literal holds a character outside of ASCII. A `char` is a
+single byte, and most characters outside of ASCII take several bytes to
+encode in UTF-8.
+
+Erroneous code example:
+
+    var c: char = 'é';
+
+Write the character in a string literal, which holds its UTF-8 bytes:
+
+    var s: *char = \"é\";
+"),
+    ("E0008", "\
+A block comment was not closed before the end of the file. Block comments
+nest, so every `/*` inside one needs its own `*/`.
+
+Erroneous code example:
+
+    /* outer /* inner */
+    fn main() -> int { 0 }
+
+Close every comment that was opened:
+
+    /* outer /* inner */ */
+    fn main() -> int { 0 }
+"),
+    ("E0009", "\
+A number is not written correctly: it has digits that do not belong to its
+base, a base prefix with no digits, an unknown suffix, or a value too large
+for 64 bits.
+
+Erroneous code example:
+
+    var mask: int = 0b102;
+
+Binary literals only use `0` and `1`, octal `0` to `7`, and hexadecimal
+`0` to `9` and `a` to `f`. The suffixes are `i8` to `i64` and `u8` to `u64`:
+
+    var mask: int = 0b101;
+"),
+    ("E0010", "\
+A token appeared where it cannot start or continue the construct being
+parsed.
+
+Erroneous code example:
+
+    var x: = 1;
+
+Here a type is missing after the `:`:
+
+    var x: int = 1;
+"),
+    ("E0011", "\
+A token that the grammar requires is missing. The error points just after
+the last token that was read, where the missing one belongs.
+
+Erroneous code example:
+
+    fn main() -> int {
+        var x: int = 1
+        x
+    }
+
+Add the missing token, here the `;` that ends a variable declaration:
+
+    fn main() -> int {
+        var x: int = 1;
+        x
+    }
+"),
+    ("E0012", "\
+A declaration was written without the name it declares.
+
+Erroneous code example:
+
+    var : int = 1;
+
+Give the declaration a name:
+
+    var count: int = 1;
+"),
+    ("E0013", "\
+An identifier was expected, for example the name of a function, parameter or
+variable, but something else was found.
+
+Erroneous code example:
+
+    fn while(x: int) -> int { x + x }
+
+Write a name in its place. A name starts with a letter or `_`, goes on with
+letters, digits and `_`, and is not a keyword:
+
+    fn double(x: int) -> int { x + x }
+"),
+    ("E0014", "\
+The file ended in the middle of a declaration or expression.
+
+Erroneous code example:
+
+    fn main() -> int {
+        1 +
+
+Finish the construct and close every open brace and parenthesis:
+
+    fn main() -> int {
+        1 + 2
+    }
+"),
+    ("E0015", "\
+An assignment, compound assignment or `&` was applied to something that does
+not name a memory location. Only variables and dereferenced pointers can be
+assigned to or have their address taken.
+
+Erroneous code example:
+
+    fn main() -> int {
+        var x: int = 1;
+        x + 1 = 3;
+        x
+    }
+
+Assign to a variable, or through a pointer:
+
+    fn main() -> int {
+        var x: int = 1;
+        x = 3;
+        x
+    }
+"),
+    ("E0016", "\
+A name was used that is not declared in any enclosing scope.
+
+Erroneous code example:
+
+    fn main() -> int {
+        count + 1
+    }
+
+Declare the variable before using it:
+
+    fn main() -> int {
+        var count: int = 0;
+        count + 1
+    }
+"),
+    ("E0017", "\
+A name was declared twice in the same scope. Inner blocks may shadow a name,
+but one scope can only declare it once.
+
+Erroneous code example:
+
+    fn main() -> int {
+        var x: int = 1;
+        var x: int = 2;
+        x
+    }
+
+Rename one of them, or assign instead of declaring again:
+
+    fn main() -> int {
+        var x: int = 1;
+        x = 2;
+        x
+    }
+"),
+    ("E0018", "\
+An expression has a different type than the place it is used in requires.
+
+Erroneous code example:
+
+    fn name() -> int {
+        return \"cx\";
+    }
+
+Make the types agree, by changing either the expression or the declared
+type:
+
+    fn name() -> *char {
+        return \"cx\";
+    }
+"),
+    ("E0019", "\
+A `&T` reference was written through, or given where a `*T` pointer is
+expected. A reference can only be read through, so it does not turn into a
+pointer that could be written through.
+
+Erroneous code example:
+
+    fn bump(counter: &int) {
+        *counter += 1;
+    }
+
+Take a pointer when the value is changed, a pointer still converts into a
+reference where only reading is needed:
+
+    fn bump(counter: *int) {
+        *counter += 1;
+    }
+"),
+    ("E0020", "\
+An integer literal does not fit in its type. Literals without a suffix are
+`int`, a 64-bit signed integer.
+
+Erroneous code example:
+
+    var small: u8 = 300u8;
+
+Use a value inside the type's range, or a wider type:
+
+    var small: u16 = 300u16;
+"),
+    ("E0021", "\
+An operator was applied to an operand whose type does not support it, or a
+value that is not a function was called with `()`.
+
+Erroneous code example:
+
+    fn main() -> int {
+        var s: *char = \"text\";
+        ~s;
+        0
+    }
+
+Only apply operators to types they are defined for, `~` works on integers:
+
+    fn main() -> int {
+        var n: int = 5;
+        ~n;
+        0
+    }
+"),
+    ("E0022", "\
+`break` or `continue` was used where no loop encloses it. They only apply to
+the innermost `while`, `do`-`while` or `for` loop around them.
+
+Erroneous code example:
+
+    fn main() -> int {
+        var n: int = 0;
+        if n == 0 {
+            break;
+        }
+        n
+    }
+
+Put the statement inside a loop, or leave the function early instead:
+
+    fn main() -> int {
+        var n: int = 0;
+        while n < 10 {
+            if n == 5 {
+                break;
+            }
+            n += 1;
+        }
+        n
+    }
+"),
+    ("E0023", "\
+`break` or `continue` names a label that no enclosing loop carries.
+
+Erroneous code example:
+
+    fn main() -> int {
+        'outer: while 1 {
+            while 1 {
+                break 'outr;
+            }
+        }
+        0
+    }
+
+Use the label of one of the loops around the statement:
+
+    fn main() -> int {
+        'outer: while 1 {
+            while 1 {
+                break 'outer;
+            }
+        }
+        0
+    }
+"),
+    ("E0024", "\
+A loop has the same label as a loop around it, so `break` and `continue`
+could not tell them apart.
+
+Erroneous code example:
+
+    fn main() -> int {
+        'scan: while 1 {
+            'scan: while 1 {
+                break 'scan;
+            }
+        }
+        0
+    }
+
+Give the inner loop a label of its own:
+
+    fn main() -> int {
+        'scan: while 1 {
+            'row: while 1 {
+                break 'scan;
+            }
+        }
+        0
+    }
+"),
+    ("E0025", "\
+Two arms of a switch match the same value, or a switch has more than one
+`default` arm. Only the first arm could ever run for that value.
+
+Erroneous code example:
+
+    fn kind(c: char) -> int {
+        switch c {
+            case 'a', 'e' { 1 }
+            case 'e' { 2 }
+            default { 0 }
+        }
+    }
+
+List every value in one arm only:
+
+    fn kind(c: char) -> int {
+        switch c {
+            case 'a', 'e' { 1 }
+            case 'i' { 2 }
+            default { 0 }
+        }
+    }
+"),
+    ("E0026", "\
+`fallthrough` was used outside of a switch arm, or in the last arm of a
+switch where there is no arm to continue with.
+
+Erroneous code example:
+
+    fn main() -> int {
+        var n: int = 0;
+        switch n {
+            case 0 { n = 1; }
+            default { fallthrough; }
+        }
+        n
+    }
+
+Only use `fallthrough` in an arm that another arm follows:
+
+    fn main() -> int {
+        var n: int = 0;
+        switch n {
+            case 0 { n = 1; fallthrough; }
+            default { n += 1; }
+        }
+        n
+    }
+"),
+    ("E0027", "\
+A function was declared inside the body of another function. Functions can
+only be declared at the top level of a file.
+
+Erroneous code example:
+
+    fn main() -> int {
+        fn twice(x: int) -> int { x * 2 }
+        twice(2)
+    }
+
+Move the function out of the body:
+
+    fn twice(x: int) -> int { x * 2 }
+
+    fn main() -> int {
+        twice(2)
+    }
+"),
+    ("E0028", "\
+An expression was written at the top level of a file. Only functions and
+global variables are declared there, code has to be inside a function to
+run.
+
+Erroneous code example:
+
+    var count: int = 0;
+    count = 1;
+
+Give the global its value in the declaration, or assign it in a function:
+
+    var count: int = 1;
+"),
+    ("E0029", "\
+A global variable was initialized with something that has to run to be
+known, like a call or another variable. Globals are laid out before the
+program starts, so their initializer may only use literals and operators,
+or be a string literal.
+
+Erroneous code example:
+
+    fn answer() -> int { 42 }
+
+    var cached: int = answer();
+
+Use a constant, or assign the value at the start of `main`:
+
+    fn answer() -> int { 42 }
+
+    var cached: int = 0;
+
+    fn main() -> int {
+        cached = answer();
+        0
+    }
+"),
+    ("E0030", "\
+A function declared to return a value can reach the end of its body without
+one. Every path through the body must end in a value of the return type or
+in a `return`.
+
+Erroneous code example:
+
+    fn sign(n: int) -> int {
+        if n < 0 {
+            return -1;
+        }
+    }
+
+End the body in a value, or give the `if` an `else` arm that has one:
+
+    fn sign(n: int) -> int {
+        if n < 0 {
+            return -1;
+        }
+        1
+    }
+"),
+];
+
+// the explanation of `code`, codes are matched case-insensitively
+pub fn explanation(code: &str) -> Option<&'static str> {
+    let code = code.to_ascii_uppercase();
+    EXPLANATIONS.iter().find(|(c, _)| *c == code).map(|(_, text)| *text)
+}
+
@@ -0,0 +1,69 @@
+use crate::diag::diag::Diagnostic;
+use crate::parser::source_map::SourceMap;
+use crate::parser::token::Span;
+
+// Writes diagnostics for tools, one object per line:
+//   {"code": "E0020" | null, "severity": "error" | "warning" | "note",
+//    "lint": "unused_variables" | null, "message": "...",
+//    "spans": [{"file", "line_start", "column_start", "line_end", "column_end",
+//               "byte_start", "byte_end", "is_primary", "label": "..." | null}],
+//    "notes": ["..."], "suggestions": ["..."]}
+// lines and columns count from 1, columns in characters, byte ranges are half-open.
+// the primary span always comes first, fields are only ever added
+pub fn to_json(sources: &SourceMap, diag: &Diagnostic) -> String {
+    let mut spans = vec![span_json(sources, &diag.span, true, None)];
+    for label in diag.labels.iter() {
+        spans.push(span_json(sources, &label.span, false, Some(&label.message)));
+    }
+
+    format!(
+        "{{\"code\":{},\"severity\":{},\"lint\":{},\"message\":{},\"spans\":[{}],\"notes\":{},\"suggestions\":{}}}",
+        diag.code.map(string).unwrap_or_else(|| "null".to_string()),
+        string(diag.severity.name()),
+        diag.lint.map(|lint| string(lint.name())).unwrap_or_else(|| "null".to_string()),
+        string(&diag.message),
+        spans.join(","),
+        strings(&diag.notes),
+        strings(&diag.help),
+    )
+}
+
+fn span_json(sources: &SourceMap, span: &Span, primary: bool, label: Option<&str>) -> String {
+    let start = sources.location(span);
+    let end = sources.end_location(span);
+    format!(
+        "{{\"file\":{},\"line_start\":{},\"column_start\":{},\"line_end\":{},\"column_end\":{},\"byte_start\":{},\"byte_end\":{},\"is_primary\":{},\"label\":{}}}",
+        string(sources.path(span)),
+        start.line,
+        start.column,
+        end.line,
+        end.column,
+        span.start(),
+        span.end(),
+        primary,
+        label.map(string).unwrap_or_else(|| "null".to_string()),
+    )
+}
+
+fn strings(items: &[String]) -> String {
+    let items: Vec<String> = items.iter().map(|s| string(s)).collect();
+    format!("[{}]", items.join(","))
+}
+
+// a JSON string literal holding `s`
+fn string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -1 +1,5 @@
+#[allow(clippy::module_inception)]
 pub mod diag;
+pub mod render;
+pub mod json;
+pub mod explain;
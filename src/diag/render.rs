@@ -0,0 +1,154 @@
+use crate::diag::diag::{Diagnostic, Severity};
+use crate::parser::source_map::{SourceFile, SourceMap};
+use crate::parser::token::Span;
+
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const GREEN: &str = "\x1b[1;32m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+const TAB_WIDTH: usize = 4;
+
+// turns diagnostics into rustc-style reports with the offending source lines
+pub struct Renderer<'a> {
+    sources: &'a SourceMap,
+    color: bool,
+}
+
+// one underlined span, the primary one says where the error is
+struct Annotation<'a> {
+    span: &'a Span,
+    label: String,
+    primary: bool,
+}
+
+impl<'a> Renderer<'a> {
+    pub fn new(sources: &'a SourceMap, color: bool) -> Self {
+        Renderer { sources, color }
+    }
+
+    fn paint(&self, style: &str, text: &str) -> String {
+        if self.color {
+            format!("{}{}{}", style, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+
+    pub fn render(&self, diag: &Diagnostic) -> String {
+        let color = severity_color(diag.severity);
+        let mut annotations = vec![Annotation { span: &diag.span, label: String::new(), primary: true }];
+        for label in diag.labels.iter() {
+            annotations.push(Annotation { span: &label.span, label: label.message.clone(), primary: false });
+        }
+
+        // the gutter fits the largest line number shown
+        let width = annotations.iter()
+            .map(|a| self.sources.end_location(a.span).line.to_string().len())
+            .max()
+            .unwrap_or(1);
+
+        let title = match diag.code {
+            Some(code) => format!("{}[{}]", diag.severity.name(), code),
+            None => diag.severity.name().to_string(),
+        };
+        let mut out = format!("{}{}\n", self.paint(color, &title), self.paint(BOLD, &format!(": {}", diag.message)));
+
+        // the primary file comes first, every other file gets its own section
+        let mut files = vec![diag.span.file()];
+        for a in annotations.iter() {
+            if !files.contains(&a.span.file()) {
+                files.push(a.span.file());
+            }
+        }
+        for (i, file) in files.into_iter().enumerate() {
+            let mut in_file: Vec<&Annotation> = annotations.iter().filter(|a| a.span.file() == file).collect();
+            in_file.sort_by_key(|a| a.span.start());
+            // the section is headed by its first annotation, the primary one in the first section
+            let head = if i == 0 { annotations[0].span } else { in_file[0].span };
+            let loc = self.sources.location(head);
+            out += &format!(
+                "{}{} {}:{}:{}\n",
+                " ".repeat(width),
+                self.paint(BLUE, if i == 0 { "-->" } else { ":::" }),
+                self.sources.path(head),
+                loc.line,
+                loc.column,
+            );
+            out += &self.render_snippet(self.sources.file(file), &in_file, width, color);
+        }
+
+        let mut notes = diag.notes.clone();
+        match (diag.lint, diag.severity) {
+            (Some(lint), Severity::Warning) => notes.push(format!("`-A {}` silences this warning", lint.name())),
+            (Some(lint), Severity::Error) => notes.push(format!("`{}` is an error because of `-Werror`", lint.name())),
+            _ => {}
+        }
+        for (kind, text) in notes.iter().map(|n| ("note", n)).chain(diag.help.iter().map(|h| ("help", h))) {
+            let kind = self.paint(BOLD, &format!("{}:", kind));
+            out += &format!("{} {} {} {}\n", " ".repeat(width), self.paint(BLUE, "="), kind, text);
+        }
+        out
+    }
+
+    fn render_snippet(&self, file: &SourceFile, annotations: &[&Annotation], width: usize, color: &str) -> String {
+        let gutter = |line: &str| self.paint(BLUE, &format!("{:>width$} |", line, width = width));
+        let mut out = format!("{}\n", gutter(""));
+        let mut last_line = None;
+
+        for annotation in annotations {
+            let start = file.location(annotation.span.start());
+            let end = file.location(annotation.span.end());
+            let text = file.line(start.line);
+
+            if last_line != Some(start.line) {
+                if last_line.is_some_and(|last| start.line > last + 1) {
+                    out += &format!("{}\n", self.paint(BLUE, "..."));
+                }
+                out += &format!("{} {}\n", gutter(&start.line.to_string()), expand_tabs(text));
+                last_line = Some(start.line);
+            }
+
+            // spans over several lines are underlined to the end of their first line
+            let prefix: String = text.chars().take(start.column - 1).collect();
+            let count = if end.line == start.line {
+                end.column - start.column
+            } else {
+                text.chars().count() + 1 - start.column
+            };
+            let (marker, style) = if annotation.primary { ("^", color) } else { ("-", BLUE) };
+            let underlined: String = text.chars().skip(start.column - 1).take(count).collect();
+            let mut underline = marker.repeat(display_width(&underlined).max(1));
+            if !annotation.label.is_empty() {
+                underline = format!("{} {}", underline, annotation.label);
+            }
+            out += &format!(
+                "{} {}{}\n",
+                gutter(""),
+                " ".repeat(display_width(&prefix)),
+                self.paint(style, &underline),
+            );
+        }
+        out
+    }
+}
+
+fn severity_color(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => RED,
+        Severity::Warning => YELLOW,
+        Severity::Note => GREEN,
+    }
+}
+
+// tabs are shown as spaces so the underline lines up with the text
+fn expand_tabs(text: &str) -> String {
+    text.replace('\t', &" ".repeat(TAB_WIDTH))
+}
+
+// how many columns `text` takes up once tabs are expanded
+fn display_width(text: &str) -> usize {
+    text.chars().map(|c| if c == '\t' { TAB_WIDTH } else { 1 }).sum()
+}
@@ -0,0 +1,259 @@
+use std::io::{BufWriter, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use crate::codegen::codegen::State;
+use crate::diag::diag::{Diag, Diagnostic, Severity};
+use crate::diag::{json, render::Renderer};
+use crate::driver::options::{ColorChoice, Emit, ErrorFormat, Input, Options};
+use crate::driver::toolchain::{self, LinkerKind};
+use crate::ir::lower::lower;
+use crate::parser::{ast, lib::Lexer, parser::Parser};
+use crate::parser::source_map::SourceMap;
+use crate::parser::token::{FileId, Token};
+use crate::sema::sema;
+
+fn read_input(input: &Input) -> Result<String, String> {
+    let name = input.name();
+    match input {
+        Input::Stdin => {
+            let mut text = String::new();
+            std::io::stdin()
+                .read_to_string(&mut text)
+                .map_err(|e| format!("could not read {}: {}", name, e))?;
+            Ok(text)
+        }
+        Input::File(path) => std::fs::read_to_string(path)
+            .map_err(|e| format!("could not read {}: {}", name, e)),
+    }
+}
+
+// how diagnostics are written to stderr
+enum Reporter<'a> {
+    Human(Renderer<'a>),
+    Json(&'a SourceMap),
+}
+
+impl Reporter<'_> {
+    fn report(&self, diagnostics: &[Diagnostic]) {
+        for diagnostic in diagnostics {
+            match self {
+                Reporter::Human(renderer) => eprintln!("{}", renderer.render(diagnostic)),
+                Reporter::Json(sources) => eprintln!("{}", json::to_json(sources, diagnostic)),
+            }
+        }
+    }
+
+    fn report_errors(&self, errors: Vec<Diag>) {
+        let diagnostics: Vec<Diagnostic> = errors.into_iter().map(Diagnostic::from).collect();
+        self.report(&diagnostics);
+    }
+}
+
+// drops allowed lints and applies -Werror
+fn apply_lint_levels(options: &Options, diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+    diagnostics
+        .into_iter()
+        .filter(|d| d.lint.is_none_or(|lint| !options.allowed.contains(&lint)))
+        .map(|mut d| {
+            if options.warnings_as_errors && d.severity == Severity::Warning {
+                d.severity = Severity::Error;
+            }
+            d
+        })
+        .collect()
+}
+
+fn use_color(choice: ColorChoice) -> bool {
+    match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+    }
+}
+
+// why the pipeline stopped early
+enum Failure {
+    // diagnostics were reported already
+    Diagnostics,
+    Fatal(String),
+}
+
+// lexes `file` and reports its lexical errors, the tokens are usable either way
+fn lex(sources: &SourceMap, reporter: &Reporter, file: FileId) -> (Vec<Token>, bool) {
+    let (tokens, errors) = Lexer::new(sources.file(file).text().to_string(), file).tokenize();
+    let ok = errors.is_empty();
+    reporter.report_errors(errors);
+    (tokens, ok)
+}
+
+// every input is parsed, even after a failing one, so that all errors are reported at once.
+// the enums of a file can be used by the files after it
+fn parse(sources: &SourceMap, reporter: &Reporter) -> Result<Vec<ast::DeclType>, Failure> {
+    let mut program: Vec<ast::DeclType> = Vec::new();
+    let mut enums = ast::Enums::new();
+    let mut failed = false;
+
+    for (file, _) in sources.files() {
+        let (tokens, ok) = lex(sources, reporter, file);
+        failed |= !ok;
+        let mut parser = Parser::new(tokens).with_enums(enums);
+        let parsed = parser.parse_program();
+        enums = parser.take_enums();
+        match parsed {
+            Ok(decls) => program.extend(decls),
+            Err(errors) => {
+                reporter.report_errors(errors);
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        return Err(Failure::Diagnostics);
+    }
+    Ok(program)
+}
+
+// where the final artifact goes, None for stdout
+fn output_path(options: &Options) -> Option<PathBuf> {
+    if let Some(path) = &options.output {
+        return Some(path.clone());
+    }
+    let stem = match options.inputs.first() {
+        Some(Input::File(path)) => path.file_stem().map(PathBuf::from),
+        _ => None,
+    };
+    match options.emit {
+        Emit::Exe => Some(stem.unwrap_or_else(|| PathBuf::from("a.out"))),
+        Emit::Obj => Some(stem.unwrap_or_else(|| PathBuf::from("a")).with_extension("o")),
+        _ => None,
+    }
+}
+
+type Contents<'a> = Box<dyn FnOnce(&mut dyn Write) -> std::io::Result<()> + 'a>;
+
+// writes `contents` to the file at `path`, or to stdout without one
+fn write_output(path: Option<&Path>, contents: Contents) -> Result<(), Failure> {
+    let (name, result) = match path {
+        Some(path) => (
+            path.display().to_string(),
+            std::fs::File::create(path).and_then(|file| {
+                let mut writer = BufWriter::new(file);
+                contents(&mut writer)?;
+                writer.flush()
+            }),
+        ),
+        None => {
+            let mut stdout = std::io::stdout().lock();
+            ("stdout".to_string(), contents(&mut stdout).and_then(|()| stdout.flush()))
+        }
+    };
+    result.map_err(|e| Failure::Fatal(format!("could not write {}: {}", name, e)))
+}
+
+// a per-process directory for intermediate files, removed when dropped
+struct BuildDir(PathBuf);
+
+impl BuildDir {
+    fn new() -> Result<BuildDir, Failure> {
+        let path = std::env::temp_dir().join(format!("cx-{}", std::process::id()));
+        std::fs::create_dir_all(&path)
+            .map_err(|e| Failure::Fatal(format!("could not create {}: {}", path.display(), e)))?;
+        Ok(BuildDir(path))
+    }
+
+    fn file(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+}
+
+impl Drop for BuildDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+// turns the assembly at `asm_path` into an object file or executable at `output`
+fn build(options: &Options, dir: &BuildDir, asm_path: &Path, output: &Path) -> Result<(), Failure> {
+    if options.emit == Emit::Obj {
+        return toolchain::assemble(&options.assembler, asm_path, output).map_err(Failure::Fatal);
+    }
+    let obj_path = dir.file("out.o");
+    toolchain::assemble(&options.assembler, asm_path, &obj_path).map_err(Failure::Fatal)?;
+    toolchain::link(&options.linker, &obj_path, output).map_err(Failure::Fatal)
+}
+
+// runs the pipeline up to the requested stage and writes that stage's output
+fn compile(options: &Options, sources: &SourceMap) -> Result<(), Failure> {
+    let output = output_path(options);
+    let write = |contents| write_output(output.as_deref(), contents);
+    let reporter = match options.error_format {
+        ErrorFormat::Human => Reporter::Human(Renderer::new(sources, use_color(options.color))),
+        ErrorFormat::Json => Reporter::Json(sources),
+    };
+
+    if options.emit == Emit::Tokens {
+        let lexed: Vec<(Vec<Token>, bool)> = sources.files().map(|(file, _)| lex(sources, &reporter, file)).collect();
+        // like every other stage, nothing is written once an error was reported
+        if lexed.iter().any(|(_, ok)| !ok) {
+            return Err(Failure::Diagnostics);
+        }
+        return write(Box::new(|out| {
+            for token in lexed.iter().flat_map(|(tokens, _)| tokens.iter()) {
+                writeln!(out, "{}", token.display())?;
+            }
+            Ok(())
+        }));
+    }
+
+    let program = parse(sources, &reporter)?;
+    if options.emit == Emit::Ast {
+        return write(Box::new(|out| writeln!(out, "{:#?}", program)));
+    }
+
+    let diagnostics = apply_lint_levels(options, sema::check(&program));
+    reporter.report(&diagnostics);
+    if diagnostics.iter().any(Diagnostic::is_error) {
+        return Err(Failure::Diagnostics);
+    }
+
+    match options.emit {
+        Emit::Tokens | Emit::Ast => unreachable!(),
+        Emit::Ir => write(Box::new(|out| write!(out, "{}", lower(&program)))),
+        Emit::Asm => write(Box::new(|out| State::new(program).generate(out))),
+        Emit::Obj | Emit::Exe => {
+            let start_stub = options.emit == Emit::Exe
+                && toolchain::linker_kind(&options.linker) == LinkerKind::Ld;
+            let output = output.unwrap();
+            let dir = BuildDir::new()?;
+            let asm_path = dir.file("out.asm");
+            write_output(Some(&asm_path), Box::new(|out| {
+                State::new(program).with_start_stub(start_stub).generate(out)
+            }))?;
+            build(options, &dir, &asm_path, &output)
+        }
+    }
+}
+
+pub fn run(options: &Options) -> ExitCode {
+    let mut sources = SourceMap::new();
+    for input in options.inputs.iter() {
+        match read_input(input) {
+            Ok(text) => { sources.add(input.name(), text); }
+            Err(msg) => {
+                eprintln!("cx: error: {}", msg);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    match compile(options, &sources) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(Failure::Diagnostics) => ExitCode::FAILURE,
+        Err(Failure::Fatal(msg)) => {
+            eprintln!("cx: error: {}", msg);
+            ExitCode::FAILURE
+        }
+    }
+}
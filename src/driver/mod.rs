@@ -0,0 +1,4 @@
+#[allow(clippy::module_inception)]
+pub mod driver;
+pub mod options;
+pub mod toolchain;
@@ -0,0 +1,250 @@
+use std::path::PathBuf;
+
+use crate::diag::diag::Lint;
+
+pub const USAGE: &str = "\
+usage: cx [build] [options] <input>...
+
+inputs:
+    <file>.cx          a Cx source file
+    -                  read the source from stdin
+
+options:
+    -o <path>          write the output to <path>, executables default to
+                       the name of the first input, other stages to stdout
+    --emit=<stage>     stop after <stage> and write its output, one of
+                       tokens, ast, ir, asm, obj, exe (default)
+    --assembler=<path> the NASM executable used for obj and exe (default: nasm)
+    --linker=<path>    the linker used for exe (default: cc), a plain `ld`
+                       links without libc through a generated `_start`
+    --color=<when>     colour diagnostics: auto (default), always, never
+    --error-format=<f> human (default) or json, one object per line
+    -Werror            treat warnings as errors
+    -A <lint>          silence the warnings of <lint>, e.g. unused_variables
+    --explain <code>   explain the error with <code>, e.g. E0003
+    -h, --help         print this message";
+
+pub const DEFAULT_ASSEMBLER: &str = "nasm";
+pub const DEFAULT_LINKER: &str = "cc";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Input {
+    Stdin,
+    File(PathBuf),
+}
+
+impl Input {
+    pub fn name(&self) -> String {
+        match self {
+            Input::Stdin => "<stdin>".to_string(),
+            Input::File(path) => path.display().to_string(),
+        }
+    }
+}
+
+// the pipeline stage to stop after
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Emit {
+    Tokens,
+    Ast,
+    Ir,
+    Asm,
+    Obj,
+    #[default]
+    Exe,
+}
+
+impl Emit {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "tokens" => Some(Emit::Tokens),
+            "ast" => Some(Emit::Ast),
+            "ir" => Some(Emit::Ir),
+            "asm" => Some(Emit::Asm),
+            "obj" => Some(Emit::Obj),
+            "exe" => Some(Emit::Exe),
+            _ => None,
+        }
+    }
+}
+
+// whether diagnostics are coloured
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorChoice {
+    // only when stderr is a terminal
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "auto" => Some(ColorChoice::Auto),
+            "always" => Some(ColorChoice::Always),
+            "never" => Some(ColorChoice::Never),
+            _ => None,
+        }
+    }
+}
+
+// how diagnostics are written to stderr
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorFormat {
+    #[default]
+    Human,
+    Json,
+}
+
+impl ErrorFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "human" => Some(ErrorFormat::Human),
+            "json" => Some(ErrorFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub inputs: Vec<Input>,
+    pub output: Option<PathBuf>,
+    pub emit: Emit,
+    pub assembler: String,
+    pub linker: String,
+    pub color: ColorChoice,
+    pub error_format: ErrorFormat,
+    // promote every warning to an error
+    pub warnings_as_errors: bool,
+    // lints whose warnings are dropped
+    pub allowed: Vec<Lint>,
+    // the error code to explain instead of compiling
+    pub explain: Option<String>,
+    pub help: bool,
+}
+
+fn parse_emit(stage: &str) -> Result<Emit, String> {
+    Emit::from_name(stage).ok_or_else(|| format!("unknown emit stage '{}'", stage))
+}
+
+fn parse_lint(name: &str) -> Result<Lint, String> {
+    Lint::from_name(name).ok_or_else(|| format!("unknown lint '{}'", name))
+}
+
+// the value of `--name=value` or `--name value`, None if `arg` is not `--name`
+fn option_value<I>(name: &str, arg: &str, args: &mut I) -> Option<Result<String, String>>
+where
+    I: Iterator<Item = String>,
+{
+    if arg == name {
+        return Some(args.next().ok_or_else(|| format!("'{}' expects a value", name)));
+    }
+    let value = arg.strip_prefix(name)?.strip_prefix('=')?;
+    Some(Ok(value.to_string()))
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            inputs: Vec::new(),
+            output: None,
+            emit: Emit::default(),
+            assembler: DEFAULT_ASSEMBLER.to_string(),
+            linker: DEFAULT_LINKER.to_string(),
+            color: ColorChoice::default(),
+            error_format: ErrorFormat::default(),
+            warnings_as_errors: false,
+            allowed: Vec::new(),
+            explain: None,
+            help: false,
+        }
+    }
+}
+
+impl Options {
+    // parses everything after the program name
+    pub fn parse<I>(args: I) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut options = Options::default();
+        let mut args = args.into_iter().peekable();
+
+        // `build` is the only subcommand and may be left out
+        if args.peek().is_some_and(|arg| arg == "build") {
+            args.next();
+        }
+
+        while let Some(arg) = args.next() {
+            if let Some(stage) = option_value("--emit", &arg, &mut args) {
+                options.emit = parse_emit(&stage?)?;
+                continue;
+            }
+            if let Some(path) = option_value("--assembler", &arg, &mut args) {
+                options.assembler = path?;
+                continue;
+            }
+            if let Some(path) = option_value("--linker", &arg, &mut args) {
+                options.linker = path?;
+                continue;
+            }
+            if let Some(when) = option_value("--color", &arg, &mut args) {
+                let when = when?;
+                options.color = ColorChoice::from_name(&when)
+                    .ok_or_else(|| format!("unknown color choice '{}'", when))?;
+                continue;
+            }
+            if let Some(code) = option_value("--explain", &arg, &mut args) {
+                options.explain = Some(code?);
+                continue;
+            }
+            if let Some(format) = option_value("--error-format", &arg, &mut args) {
+                let format = format?;
+                options.error_format = ErrorFormat::from_name(&format)
+                    .ok_or_else(|| format!("unknown error format '{}'", format))?;
+                continue;
+            }
+
+            match arg.as_str() {
+                "-h" | "--help" => options.help = true,
+                "-Werror" => options.warnings_as_errors = true,
+                "-A" => {
+                    let Some(name) = args.next() else {
+                        return Err("'-A' expects a lint name".to_string());
+                    };
+                    options.allowed.push(parse_lint(&name)?);
+                }
+                "-o" => {
+                    let Some(path) = args.next() else {
+                        return Err("'-o' expects an output path".to_string());
+                    };
+                    options.output = Some(PathBuf::from(path));
+                }
+                "-" => options.inputs.push(Input::Stdin),
+                _ if arg.starts_with("-A") => {
+                    options.allowed.push(parse_lint(&arg[2..])?);
+                }
+                _ if arg.starts_with("-o") => {
+                    options.output = Some(PathBuf::from(&arg[2..]));
+                }
+                _ if arg.starts_with('-') => {
+                    return Err(format!("unknown option '{}'", arg));
+                }
+                _ => options.inputs.push(Input::File(PathBuf::from(arg))),
+            }
+        }
+
+        if options.help || options.explain.is_some() {
+            return Ok(options);
+        }
+        if options.inputs.is_empty() {
+            return Err("no input files".to_string());
+        }
+        if options.inputs.iter().filter(|i| **i == Input::Stdin).count() > 1 {
+            return Err("stdin ('-') can only be given once".to_string());
+        }
+        Ok(options)
+    }
+}
@@ -0,0 +1,59 @@
+use std::ffi::OsStr;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::process::Command;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkerKind {
+    // `cc`, `gcc`, `clang`: links against libc, which provides `_start`
+    CompilerDriver,
+    // a bare `ld`: no libc, the program brings its own `_start`
+    Ld,
+}
+
+pub fn linker_kind(linker: &str) -> LinkerKind {
+    let name = Path::new(linker)
+        .file_name()
+        .and_then(OsStr::to_str)
+        .unwrap_or(linker);
+    if name == "ld" || name.starts_with("ld.") {
+        LinkerKind::Ld
+    } else {
+        LinkerKind::CompilerDriver
+    }
+}
+
+// `role` names the tool in messages and is also the name of the option that configures it
+fn run_tool(role: &str, program: &str, args: &[&OsStr]) -> Result<(), String> {
+    let output = Command::new(program).args(args).output().map_err(|e| {
+        if e.kind() == ErrorKind::NotFound {
+            format!("{} '{}' not found, install it or pass --{}=<path>", role, program, role)
+        } else {
+            format!("could not run {} '{}': {}", role, program, e)
+        }
+    })?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("{} '{}' failed ({})\n{}", role, program, output.status, stderr.trim_end()));
+    }
+    Ok(())
+}
+
+pub fn assemble(assembler: &str, asm: &Path, obj: &Path) -> Result<(), String> {
+    run_tool("assembler", assembler, &[
+        OsStr::new("-f"), OsStr::new("elf64"),
+        asm.as_os_str(),
+        OsStr::new("-o"), obj.as_os_str(),
+    ])
+}
+
+pub fn link(linker: &str, obj: &Path, exe: &Path) -> Result<(), String> {
+    let mut args = vec![];
+    if linker_kind(linker) == LinkerKind::CompilerDriver {
+        // the generated code addresses externs directly, not through the PLT
+        args.push(OsStr::new("-no-pie"));
+    }
+    args.extend([obj.as_os_str(), OsStr::new("-o"), exe.as_os_str()]);
+    run_tool("linker", linker, &args)
+}
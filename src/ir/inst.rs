@@ -1,8 +1,11 @@
+use std::fmt;
 
+use crate::parser::ast::{BinaryOperator, UnaryOperator};
 
 pub struct Module {
     // all string values are indexes into here
-    string_pool: Vec<String>, 
+    string_pool: Vec<String>,
+    labels: Vec<Label>,
 }
 
 // A Label can be of multiple types
@@ -11,6 +14,7 @@ pub struct Module {
 // * global-label
 // * function-label
 // ... and so on
+#[allow(clippy::enum_variant_names)]
 pub enum Label {
    ExternLabel (usize),
    GlobalLabel (usize),
@@ -20,10 +24,191 @@ pub enum Label {
    }
 }
 
+// virtual registers, numbered per function
+pub type Value = usize;
+
 pub struct Builder {
-    instructions: Vec<Instruction>
+    instructions: Vec<Instruction>,
+    // names (string pool ids) of the parameters, in order
+    params: Vec<usize>,
+    // names (string pool ids) of every stack slot, indexed by slot
+    locals: Vec<usize>,
+    next_value: Value,
+    next_block: Block,
 }
 
+// jump targets, numbered per function
+pub type Block = usize;
+
 pub enum Instruction {
-    
+    Const { dst: Value, value: i64 },
+    // address of a string literal, `id` is the escaped text in the string pool
+    Str { dst: Value, id: usize },
+    // address of a global or external symbol
+    Symbol { dst: Value, name: usize },
+    Load { dst: Value, slot: usize },
+    Store { slot: usize, src: Value },
+    // address of a stack slot
+    Addr { dst: Value, slot: usize },
+    LoadPtr { dst: Value, ptr: Value },
+    StorePtr { ptr: Value, src: Value },
+    Unary { dst: Value, op: UnaryOperator, src: Value },
+    Binary { dst: Value, op: BinaryOperator, lhs: Value, rhs: Value },
+    Call { dst: Value, callee: Value, args: Vec<Value> },
+    Ret { value: Option<Value> },
+    // starts `block`, jumps land here
+    Label { block: Block },
+    Jump { target: Block },
+    // goes to `then_block` if `cond` is not zero, to `else_block` otherwise
+    Branch { cond: Value, then_block: Block, else_block: Block },
+    // goes to the block of the case equal to `value`, to `default` if there is none
+    Switch { value: Value, cases: Vec<(i64, Block)>, default: Block },
+}
+
+impl Module {
+    pub fn new() -> Self {
+        Module { string_pool: Vec::new(), labels: Vec::new() }
+    }
+
+    // returns the id of `s`, adding it to the pool if it is not there yet
+    pub fn intern(&mut self, s: &str) -> usize {
+        if let Some(id) = self.string_pool.iter().position(|e| e == s) {
+            return id;
+        }
+        self.string_pool.push(s.to_string());
+        self.string_pool.len() - 1
+    }
+
+    pub fn resolve(&self, id: usize) -> &str {
+        &self.string_pool[id]
+    }
+
+    pub fn add_label(&mut self, label: Label) {
+        self.labels.push(label);
+    }
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Builder { instructions: Vec::new(), params: Vec::new(), locals: Vec::new(), next_value: 0, next_block: 0 }
+    }
+
+    pub fn new_block(&mut self) -> Block {
+        self.next_block += 1;
+        self.next_block - 1
+    }
+
+    pub fn new_value(&mut self) -> Value {
+        self.next_value += 1;
+        self.next_value - 1
+    }
+
+    pub fn new_local(&mut self, name: usize) -> usize {
+        self.locals.push(name);
+        self.locals.len() - 1
+    }
+
+    pub fn add_param(&mut self, name: usize) -> usize {
+        self.params.push(name);
+        self.new_local(name)
+    }
+
+    pub fn push(&mut self, inst: Instruction) {
+        self.instructions.push(inst);
+    }
+}
+
+fn binary_op_name(op: &BinaryOperator) -> &'static str {
+    match op {
+        BinaryOperator::Add => "add",
+        BinaryOperator::Sub => "sub",
+        BinaryOperator::Mul => "mul",
+        BinaryOperator::Div => "div",
+        BinaryOperator::Mod => "mod",
+        BinaryOperator::And => "land",
+        BinaryOperator::Or => "lor",
+        BinaryOperator::BitAnd => "and",
+        BinaryOperator::BitOr => "or",
+        BinaryOperator::Xor => "xor",
+        BinaryOperator::Shl => "shl",
+        BinaryOperator::Shr => "shr",
+        BinaryOperator::Eq => "eq",
+        BinaryOperator::Neq => "neq",
+        BinaryOperator::Lt => "lt",
+        BinaryOperator::Gt => "gt",
+        BinaryOperator::Leq => "leq",
+        BinaryOperator::Geq => "geq",
+        BinaryOperator::Assign => "assign",
+    }
+}
+
+fn unary_op_name(op: &UnaryOperator) -> &'static str {
+    match op {
+        UnaryOperator::Neg => "neg",
+        UnaryOperator::Not => "lnot",
+        UnaryOperator::BitNot => "not",
+        UnaryOperator::Deref => "deref",
+        UnaryOperator::AddrOf => "addr",
+    }
+}
+
+impl Module {
+    fn fmt_inst(&self, f: &mut fmt::Formatter, builder: &Builder, inst: &Instruction) -> fmt::Result {
+        let slot = |s: &usize| format!("{}.{}", self.resolve(builder.locals[*s]), s);
+        match inst {
+            Instruction::Const { dst, value } => write!(f, "%{} = const {}", dst, value),
+            Instruction::Str { dst, id } => write!(f, "%{} = str \"{}\"", dst, self.resolve(*id)),
+            Instruction::Symbol { dst, name } => write!(f, "%{} = symbol @{}", dst, self.resolve(*name)),
+            Instruction::Load { dst, slot: s } => write!(f, "%{} = load {}", dst, slot(s)),
+            Instruction::Store { slot: s, src } => write!(f, "store {}, %{}", slot(s), src),
+            Instruction::Addr { dst, slot: s } => write!(f, "%{} = addr {}", dst, slot(s)),
+            Instruction::LoadPtr { dst, ptr } => write!(f, "%{} = load [%{}]", dst, ptr),
+            Instruction::StorePtr { ptr, src } => write!(f, "store [%{}], %{}", ptr, src),
+            Instruction::Unary { dst, op, src } => write!(f, "%{} = {} %{}", dst, unary_op_name(op), src),
+            Instruction::Binary { dst, op, lhs, rhs } => {
+                write!(f, "%{} = {} %{}, %{}", dst, binary_op_name(op), lhs, rhs)
+            }
+            Instruction::Call { dst, callee, args } => {
+                let args: Vec<String> = args.iter().map(|a| format!("%{}", a)).collect();
+                write!(f, "%{} = call %{}({})", dst, callee, args.join(", "))
+            }
+            Instruction::Ret { value: Some(value) } => write!(f, "ret %{}", value),
+            Instruction::Ret { value: None } => write!(f, "ret"),
+            Instruction::Label { block } => write!(f, "L{}:", block),
+            Instruction::Jump { target } => write!(f, "jmp L{}", target),
+            Instruction::Branch { cond, then_block, else_block } => {
+                write!(f, "br %{}, L{}, L{}", cond, then_block, else_block)
+            }
+            Instruction::Switch { value, cases, default } => {
+                let cases: Vec<String> = cases.iter().map(|(v, block)| format!("{}: L{}", v, block)).collect();
+                write!(f, "switch %{}, [{}], L{}", value, cases.join(", "), default)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, label) in self.labels.iter().enumerate() {
+            match label {
+                Label::ExternLabel(id) => writeln!(f, "extern @{}", self.resolve(*id))?,
+                Label::GlobalLabel(id) => writeln!(f, "global @{}", self.resolve(*id))?,
+                Label::FunctionLabel { id, builder } => {
+                    let params: Vec<&str> = builder.params.iter().map(|p| self.resolve(*p)).collect();
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    writeln!(f, "fn @{}({}):", self.resolve(*id), params.join(", "))?;
+                    for inst in builder.instructions.iter() {
+                        // labels stand out from the instructions they start
+                        let indent = if matches!(inst, Instruction::Label { .. }) { "  " } else { "    " };
+                        write!(f, "{}", indent)?;
+                        self.fmt_inst(f, builder, inst)?;
+                        writeln!(f)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,369 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::ir::inst::{Block, Builder, Instruction, Label, Module, Value};
+use crate::parser::ast;
+use crate::parser::token::escape_bytes;
+
+// Lowers the AST into the textual IR.
+// every function gets its own Builder, locals live in stack slots
+pub struct Lowerer {
+    module: Module,
+    builder: Builder,
+    scopes: Vec<HashMap<String, usize>>,
+    globals: HashSet<String>,
+    // the label and (continue, break) targets of the loops around the current expression
+    loops: Vec<(Option<String>, Block, Block)>,
+    // where `fallthrough` goes in each switch arm around the current expression
+    switch_arms: Vec<Option<Block>>,
+}
+
+// somewhere a value can be stored
+enum Place {
+    Slot(usize),
+    Ptr(Value),
+}
+
+pub fn lower(program: &[ast::DeclType]) -> Module {
+    let mut lowerer = Lowerer {
+        module: Module::new(),
+        builder: Builder::new(),
+        scopes: Vec::new(),
+        globals: HashSet::new(),
+        loops: Vec::new(),
+        switch_arms: Vec::new(),
+    };
+    for decl in program.iter() {
+        lowerer.lower_global(decl);
+    }
+    lowerer.module
+}
+
+impl Lowerer {
+    fn lookup(&self, name: &str) -> Option<usize> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name).copied())
+    }
+
+    fn declare(&mut self, name: &str) -> usize {
+        let id = self.module.intern(name);
+        let slot = self.builder.new_local(id);
+        self.scopes.last_mut().unwrap().insert(name.to_string(), slot);
+        slot
+    }
+
+    fn emit_value<F>(&mut self, f: F) -> Value
+    where
+        F: FnOnce(Value) -> Instruction,
+    {
+        let dst = self.builder.new_value();
+        self.builder.push(f(dst));
+        dst
+    }
+
+    fn lower_global(&mut self, decl: &ast::DeclType) {
+        match decl {
+            ast::DeclType::FunctionDecl { name, params, body, .. } => {
+                let id = self.module.intern(name);
+                let Some(body) = body else {
+                    self.module.add_label(Label::ExternLabel(id));
+                    return;
+                };
+
+                self.builder = Builder::new();
+                self.scopes.push(HashMap::new());
+                for param in params.iter() {
+                    let Some(pname) = param.name() else { continue };
+                    let pid = self.module.intern(pname);
+                    let slot = self.builder.add_param(pid);
+                    self.scopes.last_mut().unwrap().insert(pname.to_string(), slot);
+                }
+                let value = self.lower_expr(body);
+                self.builder.push(Instruction::Ret { value });
+                self.scopes.pop();
+
+                let builder = std::mem::replace(&mut self.builder, Builder::new());
+                self.module.add_label(Label::FunctionLabel { id, builder });
+            }
+            ast::DeclType::VariableDecl { name, .. } => {
+                self.globals.insert(name.clone());
+                let id = self.module.intern(name);
+                self.module.add_label(Label::GlobalLabel(id));
+            }
+            // variants were replaced by their values
+            ast::DeclType::EnumDecl { .. } => {}
+            ast::DeclType::SideEffect(_) => unreachable!("the parser rejects expressions outside of functions"),
+        }
+    }
+
+    // lowers a declaration inside a function body, returns its value if it has one
+    fn lower_local(&mut self, decl: &ast::DeclType) -> Option<Value> {
+        match decl {
+            ast::DeclType::VariableDecl { name, init, .. } => {
+                let value = init.as_ref().and_then(|init| self.lower_expr(init));
+                let slot = self.declare(name);
+                if let Some(src) = value {
+                    self.builder.push(Instruction::Store { slot, src });
+                }
+                None
+            }
+            ast::DeclType::SideEffect(expr) => self.lower_expr(expr),
+            ast::DeclType::FunctionDecl { .. } => unreachable!("the parser rejects nested functions"),
+            ast::DeclType::EnumDecl { .. } => unreachable!("enums are only declared at the top level"),
+        }
+    }
+
+    fn lower_place(&mut self, expr: &ast::Expr) -> Option<Place> {
+        match expr {
+            ast::Expr::Identifier { name, .. } | ast::Expr::Variable(name) => {
+                if let Some(slot) = self.lookup(name) {
+                    return Some(Place::Slot(slot));
+                }
+                let name = self.module.intern(name);
+                Some(Place::Ptr(self.emit_value(|dst| Instruction::Symbol { dst, name })))
+            }
+            ast::Expr::UnaryOp { op: ast::UnaryOperator::Deref, expr, .. } => Some(Place::Ptr(self.lower_expr(expr)?)),
+            // the parser only lets lvalues through
+            _ => unreachable!(),
+        }
+    }
+
+    fn load_place(&mut self, place: &Place) -> Value {
+        match *place {
+            Place::Slot(slot) => self.emit_value(|dst| Instruction::Load { dst, slot }),
+            Place::Ptr(ptr) => self.emit_value(|dst| Instruction::LoadPtr { dst, ptr }),
+        }
+    }
+
+    fn store_place(&mut self, place: &Place, src: Value) {
+        match *place {
+            Place::Slot(slot) => self.builder.push(Instruction::Store { slot, src }),
+            Place::Ptr(ptr) => self.builder.push(Instruction::StorePtr { ptr, src }),
+        }
+    }
+
+    fn lower_loop_body(
+        &mut self,
+        label: &Option<ast::LoopLabel>,
+        body: &ast::Expr,
+        continue_block: Block,
+        break_block: Block,
+    ) {
+        self.loops.push((label.as_ref().map(|l| l.name.clone()), continue_block, break_block));
+        self.lower_expr(body);
+        self.loops.pop();
+    }
+
+    fn store_result(&mut self, slot: Option<usize>, value: Option<Value>) {
+        if let (Some(slot), Some(src)) = (slot, value) {
+            self.builder.push(Instruction::Store { slot, src });
+        }
+    }
+
+    fn lower_expr(&mut self, expr: &ast::Expr) -> Option<Value> {
+        match expr {
+            ast::Expr::IntegerLiteral { value, .. } => {
+                let value = *value;
+                Some(self.emit_value(|dst| Instruction::Const { dst, value }))
+            }
+            ast::Expr::CharLiteral(byte) => {
+                let value = *byte as i64;
+                Some(self.emit_value(|dst| Instruction::Const { dst, value }))
+            }
+            ast::Expr::StringLiteral(bytes) => {
+                let id = self.module.intern(&escape_bytes(bytes));
+                Some(self.emit_value(|dst| Instruction::Str { dst, id }))
+            }
+            ast::Expr::Identifier { name, .. } | ast::Expr::Variable(name) => {
+                if self.lookup(name).is_some() || self.globals.contains(name) {
+                    let place = self.lower_place(expr)?;
+                    return Some(self.load_place(&place));
+                }
+                // a function, the value is its address
+                let name = self.module.intern(name);
+                Some(self.emit_value(|dst| Instruction::Symbol { dst, name }))
+            }
+            ast::Expr::BinaryOp { op: ast::BinaryOperator::Assign, lhs, rhs } => {
+                let src = self.lower_expr(rhs)?;
+                let place = self.lower_place(lhs)?;
+                self.store_place(&place, src);
+                Some(src)
+            }
+            ast::Expr::CompoundAssign { op, target, value } => {
+                let place = self.lower_place(target)?;
+                let lhs = self.load_place(&place);
+                let rhs = self.lower_expr(value)?;
+                let op = *op;
+                let result = self.emit_value(|dst| Instruction::Binary { dst, op, lhs, rhs });
+                self.store_place(&place, result);
+                Some(result)
+            }
+            ast::Expr::UnaryOp { op: ast::UnaryOperator::AddrOf, expr, .. } => {
+                match self.lower_place(expr)? {
+                    Place::Slot(slot) => Some(self.emit_value(|dst| Instruction::Addr { dst, slot })),
+                    Place::Ptr(ptr) => Some(ptr),
+                }
+            }
+            ast::Expr::UnaryOp { op: ast::UnaryOperator::Deref, expr, .. } => {
+                let ptr = self.lower_expr(expr)?;
+                Some(self.emit_value(|dst| Instruction::LoadPtr { dst, ptr }))
+            }
+            ast::Expr::BinaryOp { op, lhs, rhs } => {
+                let lhs = self.lower_expr(lhs)?;
+                let rhs = self.lower_expr(rhs)?;
+                let op = *op;
+                Some(self.emit_value(|dst| Instruction::Binary { dst, op, lhs, rhs }))
+            }
+            ast::Expr::UnaryOp { op, expr, .. } => {
+                let src = self.lower_expr(expr)?;
+                let op = *op;
+                Some(self.emit_value(|dst| Instruction::Unary { dst, op, src }))
+            }
+            ast::Expr::Call { func, args } => {
+                let callee = self.lower_expr(func)?;
+                let args = args.iter().filter_map(|arg| self.lower_expr(arg)).collect();
+                Some(self.emit_value(|dst| Instruction::Call { dst, callee, args }))
+            }
+            ast::Expr::If { cond, then_branch, else_branch } => {
+                // the arms store their value into a slot of their own, there are no phis
+                let result = expr.has_value().then(|| {
+                    let id = self.module.intern("if");
+                    self.builder.new_local(id)
+                });
+                let cond = self.lower_expr(cond)?;
+                let then_block = self.builder.new_block();
+                let else_block = self.builder.new_block();
+                let end = self.builder.new_block();
+                self.builder.push(Instruction::Branch { cond, then_block, else_block });
+
+                self.builder.push(Instruction::Label { block: then_block });
+                let value = self.lower_expr(then_branch);
+                self.store_result(result, value);
+                self.builder.push(Instruction::Jump { target: end });
+
+                self.builder.push(Instruction::Label { block: else_block });
+                if let Some(else_branch) = else_branch {
+                    let value = self.lower_expr(else_branch);
+                    self.store_result(result, value);
+                }
+                self.builder.push(Instruction::Jump { target: end });
+
+                self.builder.push(Instruction::Label { block: end });
+                let slot = result?;
+                Some(self.emit_value(|dst| Instruction::Load { dst, slot }))
+            }
+            ast::Expr::While { label, cond, body } => {
+                let start = self.builder.new_block();
+                let body_block = self.builder.new_block();
+                let end = self.builder.new_block();
+                self.builder.push(Instruction::Jump { target: start });
+                self.builder.push(Instruction::Label { block: start });
+                let cond = self.lower_expr(cond)?;
+                self.builder.push(Instruction::Branch { cond, then_block: body_block, else_block: end });
+                self.builder.push(Instruction::Label { block: body_block });
+                self.lower_loop_body(label, body, start, end);
+                self.builder.push(Instruction::Jump { target: start });
+                self.builder.push(Instruction::Label { block: end });
+                None
+            }
+            ast::Expr::DoWhile { label, body, cond } => {
+                let start = self.builder.new_block();
+                let cond_block = self.builder.new_block();
+                let end = self.builder.new_block();
+                self.builder.push(Instruction::Jump { target: start });
+                self.builder.push(Instruction::Label { block: start });
+                self.lower_loop_body(label, body, cond_block, end);
+                self.builder.push(Instruction::Jump { target: cond_block });
+                self.builder.push(Instruction::Label { block: cond_block });
+                let cond = self.lower_expr(cond)?;
+                self.builder.push(Instruction::Branch { cond, then_block: start, else_block: end });
+                self.builder.push(Instruction::Label { block: end });
+                None
+            }
+            ast::Expr::For { label, init, cond, step, body } => {
+                self.scopes.push(HashMap::new());
+                if let Some(init) = init {
+                    self.lower_local(init);
+                }
+                let start = self.builder.new_block();
+                let body_block = self.builder.new_block();
+                let step_block = self.builder.new_block();
+                let end = self.builder.new_block();
+                self.builder.push(Instruction::Jump { target: start });
+                self.builder.push(Instruction::Label { block: start });
+                match cond.as_ref().and_then(|cond| self.lower_expr(cond)) {
+                    Some(cond) => self.builder.push(Instruction::Branch { cond, then_block: body_block, else_block: end }),
+                    None => self.builder.push(Instruction::Jump { target: body_block }),
+                }
+                self.builder.push(Instruction::Label { block: body_block });
+                self.lower_loop_body(label, body, step_block, end);
+                self.builder.push(Instruction::Jump { target: step_block });
+                self.builder.push(Instruction::Label { block: step_block });
+                if let Some(step) = step {
+                    self.lower_expr(step);
+                }
+                self.builder.push(Instruction::Jump { target: start });
+                self.builder.push(Instruction::Label { block: end });
+                self.scopes.pop();
+                None
+            }
+            ast::Expr::Break { label, .. } | ast::Expr::Continue { label, .. } => {
+                let (_, continue_block, break_block) = match label {
+                    Some(label) => self.loops.iter().rev().find(|l| l.0.as_ref() == Some(&label.name)).unwrap(),
+                    None => self.loops.last().unwrap(),
+                };
+                let (continue_block, break_block) = (*continue_block, *break_block);
+                let target = if matches!(expr, ast::Expr::Break { .. }) { break_block } else { continue_block };
+                self.builder.push(Instruction::Jump { target });
+                None
+            }
+            ast::Expr::Switch { scrutinee, cases, .. } => {
+                // like `if`, the arms store their value into a slot
+                let result = expr.has_value().then(|| {
+                    let id = self.module.intern("switch");
+                    self.builder.new_local(id)
+                });
+                let value = self.lower_expr(scrutinee)?;
+                let blocks: Vec<Block> = cases.iter().map(|_| self.builder.new_block()).collect();
+                let end = self.builder.new_block();
+                let default = cases.iter().position(|case| case.is_default()).map_or(end, |i| blocks[i]);
+                let targets = cases.iter()
+                    .zip(blocks.iter())
+                    .flat_map(|(case, block)| case.values.iter().map(move |v| (v.value, *block)))
+                    .collect();
+                self.builder.push(Instruction::Switch { value, cases: targets, default });
+
+                for (i, case) in cases.iter().enumerate() {
+                    self.builder.push(Instruction::Label { block: blocks[i] });
+                    self.switch_arms.push(blocks.get(i + 1).copied());
+                    let value = self.lower_expr(&case.body);
+                    self.switch_arms.pop();
+                    self.store_result(result, value);
+                    self.builder.push(Instruction::Jump { target: end });
+                }
+
+                self.builder.push(Instruction::Label { block: end });
+                let slot = result?;
+                Some(self.emit_value(|dst| Instruction::Load { dst, slot }))
+            }
+            ast::Expr::Fallthrough { .. } => {
+                // sema rejects it without a following arm
+                let target = self.switch_arms.last().copied().flatten().unwrap();
+                self.builder.push(Instruction::Jump { target });
+                None
+            }
+            ast::Expr::Return { value, .. } => {
+                let value = value.as_ref().and_then(|value| self.lower_expr(value));
+                self.builder.push(Instruction::Ret { value });
+                None
+            }
+            ast::Expr::CompoundExpr { expressions, value: has_value, .. } => {
+                self.scopes.push(HashMap::new());
+                let mut value = None;
+                for decl in expressions.iter() {
+                    value = self.lower_local(decl);
+                }
+                self.scopes.pop();
+                if has_value.is_some() { value } else { None }
+            }
+        }
+    }
+}
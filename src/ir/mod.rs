@@ -1 +1,2 @@
 pub mod inst;
+pub mod lower;
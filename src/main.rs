@@ -2,25 +2,39 @@ mod ir;
 mod parser;
 mod diag;
 mod codegen;
+mod driver;
+mod sema;
 
-use parser::parser as p;
+use std::process::ExitCode;
 
-use crate::codegen::codegen::State;
+use driver::options::{Options, USAGE};
 
-fn main() {
-    let source = String::from("fn main() {\n var foo: int = 40;\n var ahh : int = 50;\n}");
-    let mut lexer = parser::lib::Lexer::new(source);
-    let mut tokens = Vec::new();
-    loop {
-        let token = lexer.next_token();
-        println!("{:?}", token.display());
-        if token.matches(&parser::token::TokenType::Eof) {
-            break;
+fn main() -> ExitCode {
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(msg) => {
+            eprintln!("cx: error: {}\n\n{}", msg, USAGE);
+            return ExitCode::from(2);
         }
-        tokens.push(token);
+    };
+
+    if options.help {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
     }
-    let mut parser = p::Parser::new(tokens);
-    let ast = parser.parse_program().unwrap();
-    State::new(ast).generate(); 
-}
 
+    if let Some(code) = &options.explain {
+        return match diag::explain::explanation(code) {
+            Some(text) => {
+                print!("{}", text);
+                ExitCode::SUCCESS
+            }
+            None => {
+                eprintln!("cx: error: no error code '{}'", code);
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    driver::driver::run(&options)
+}
@@ -1,6 +1,11 @@
 #![allow(dead_code)]
 
+use std::collections::HashMap;
+
+use super::token::Span;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(clippy::enum_variant_names)]
 pub enum Type {
     TypeName(String), // for typedefs or named types
     CxInteger {
@@ -42,17 +47,20 @@ pub enum Type {
     },
 }
 
+#[derive(Debug)]
 pub enum TakeType {
     ByValue,
     ByRef { mutable: bool },
 }
 
+#[derive(Debug)]
 pub struct Parameter {
     name: Option<String>,
     ptype: Box<Type>,
     take_type: TakeType,
 }
 
+#[derive(Debug)]
 pub enum DeclType {
     FunctionDecl {
         name: String,
@@ -65,18 +73,64 @@ pub enum DeclType {
         var_type: Box<Type>,
         init: Option<Box<Expr>>, // None for uninitialized
         mutability: Mutability,
+        // where the name was declared
+        span: Span,
+    },
+    // `enum Name { A, B = 4 }`, only at the top level. `span` is the name
+    EnumDecl {
+        name: String,
+        variants: Vec<EnumVariant>,
+        span: Span,
     },
     SideEffect(Expr),
 }
 
+// a variant of an enum with its value, `Name::Variant` is that value typed as the enum
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnumVariant {
+    pub name: String,
+    pub value: i64,
+    pub span: Span,
+}
+
+// the enums declared so far by name, with where they were declared
+#[derive(Debug, Default)]
+pub struct Enums(HashMap<String, (Span, Vec<EnumVariant>)>);
+
+impl Enums {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // records the enum `name`, or returns where it was declared first
+    pub fn declare(&mut self, name: &str, span: &Span, variants: &[EnumVariant]) -> Option<Span> {
+        if let Some((first, _)) = self.0.get(name) {
+            return Some(first.clone());
+        }
+        self.0.insert(name.to_string(), (span.clone(), variants.to_vec()));
+        None
+    }
+
+    // the value of `name::variant`
+    pub fn value(&self, name: &str, variant: &str) -> Option<i64> {
+        let (_, variants) = self.0.get(name)?;
+        variants.iter().find(|v| v.name == variant).map(|v| v.value)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BinaryOperator {
     Add,
     Sub,
     Mul,
     Div,
     Mod,
+    // logical `&&` and `||`
     And,
     Or,
+    // bitwise `&`, `|` and `^`
+    BitAnd,
+    BitOr,
     Xor,
     Shl,
     Shr,
@@ -89,9 +143,11 @@ pub enum BinaryOperator {
     Assign,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum UnaryOperator {
     Neg,    // -expr
     Not,    // !expr
+    BitNot, // ~expr
     Deref,  // *expr
     AddrOf, // &expr
 }
@@ -102,26 +158,104 @@ pub enum Mutability {
     Immutable,
 }
 
+#[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum Expr {
-    IntegerLiteral(i64),
-    Identifier(String), 
+    // `ty` is the suffix type, `int` when there is none, or the enum of a variant.
+    // u64 values above i64::MAX are stored with the same bits
+    IntegerLiteral {
+        value: i64,
+        ty: Box<Type>,
+    },
+    // the literal's bytes, without the terminating NUL
+    StringLiteral(Vec<u8>),
+    // typed as `char`
+    CharLiteral(u8),
+    Identifier {
+        name: String,
+        span: Span,
+    },
     Variable(String),
     BinaryOp {
         op: BinaryOperator,
         lhs: Box<Expr>,
         rhs: Box<Expr>,
     },
+    // `span` covers the operator and its operand
     UnaryOp {
         op: UnaryOperator,
         expr: Box<Expr>,
+        span: Span,
     },
     Call {
         func: Box<Expr>,
         args: Vec<Expr>,
     },
+    // `target op= value`, plain `=` is a BinaryOp with BinaryOperator::Assign
+    CompoundAssign {
+        op: BinaryOperator,
+        target: Box<Expr>,
+        value: Box<Expr>,
+    },
+    // `if cond { .. } else { .. }`, `else if` chains nest another If as the else branch
+    If {
+        cond: Box<Expr>,
+        then_branch: Box<Expr>,
+        else_branch: Option<Box<Expr>>,
+    },
+    While {
+        label: Option<LoopLabel>,
+        cond: Box<Expr>,
+        body: Box<Expr>,
+    },
+    // `do { .. } while cond`, the body runs at least once
+    DoWhile {
+        label: Option<LoopLabel>,
+        body: Box<Expr>,
+        cond: Box<Expr>,
+    },
+    // `for init; cond; step { .. }`, a missing cond loops forever
+    For {
+        label: Option<LoopLabel>,
+        init: Option<Box<DeclType>>,
+        cond: Option<Box<Expr>>,
+        step: Option<Box<Expr>>,
+        body: Box<Expr>,
+    },
+    // `break` or `break 'outer`, without a label the innermost loop is left
+    Break {
+        label: Option<LoopLabel>,
+        span: Span,
+    },
+    Continue {
+        label: Option<LoopLabel>,
+        span: Span,
+    },
+    // `switch value { case 1, 2 { .. } default { .. } }`, only the matching arm runs
+    Switch {
+        scrutinee: Box<Expr>,
+        // where the scrutinee was written
+        span: Span,
+        cases: Vec<SwitchCase>,
+    },
+    // ends the current arm of a switch by going on with the body of the next one
+    Fallthrough {
+        span: Span,
+    },
+    // `return expr` or a bare `return` in a function returning void.
+    // `span` covers the value, or the `return` itself when there is none
+    Return {
+        value: Option<Box<Expr>>,
+        span: Span,
+    },
     // Function Bodies, Temporay Scope, etc.
     CompoundExpr {
-        expressions: Vec<Box<DeclType>>,
+        expressions: Vec<DeclType>,
+        // where the value of the block was written, when the last expression
+        // was left without a ';'
+        value: Option<Span>,
+        // the closing '}'
+        end: Span,
     }
     // TODO: more expression types
 }
@@ -132,6 +266,31 @@ pub struct FieldMeta {
     offset: usize,
 }
 
+// one arm of a switch, the `default` arm has no values
+#[derive(Debug)]
+pub struct SwitchCase {
+    pub values: Vec<CaseValue>,
+    // the `case` or `default` keyword
+    pub span: Span,
+    pub body: Box<Expr>,
+}
+
+// a constant after `case`, chars are stored as their byte
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaseValue {
+    pub value: i64,
+    // `int` or the suffix type for integers, `char`, or the enum of a variant
+    pub ty: Type,
+    pub span: Span,
+}
+
+// `'name` in front of a loop or after `break`/`continue`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoopLabel {
+    pub name: String,
+    pub span: Span,
+}
+
 impl Parameter {
     pub fn new_named(name: String, ptype: Box<Type>, take_type: TakeType) -> Self {
         Parameter { name: Some(name), ptype, take_type }
@@ -140,6 +299,169 @@ impl Parameter {
     pub fn new_unnamed(ptype: Box<Type>, take_type: TakeType) -> Self {
         Parameter { name: None, ptype, take_type }
     } 
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn ptype(&self) -> &Type {
+        &self.ptype
+    }
+}
+
+impl Expr {
+    // where the expression was written, for the expressions that keep it
+    pub fn span(&self) -> Option<&Span> {
+        match self {
+            Expr::Identifier { span, .. } | Expr::UnaryOp { span, .. } => Some(span),
+            _ => None,
+        }
+    }
+
+    // whether the expression names a memory location that can be assigned to
+    pub fn is_lvalue(&self) -> bool {
+        matches!(self, Expr::Identifier { .. } | Expr::Variable(_) | Expr::UnaryOp { op: UnaryOperator::Deref, .. })
+    }
+
+    // the value of an integer expression made only of literals, None if it needs to run
+    pub fn const_value(&self) -> Option<i64> {
+        match self {
+            Expr::IntegerLiteral { value, .. } => Some(*value),
+            Expr::CharLiteral(byte) => Some(*byte as i64),
+            Expr::UnaryOp { op, expr, .. } => {
+                let value = expr.const_value()?;
+                match op {
+                    UnaryOperator::Neg => Some(value.wrapping_neg()),
+                    UnaryOperator::BitNot => Some(!value),
+                    UnaryOperator::Not => Some((value == 0) as i64),
+                    UnaryOperator::Deref | UnaryOperator::AddrOf => None,
+                }
+            }
+            Expr::BinaryOp { op, lhs, rhs } => {
+                let (lhs, rhs) = (lhs.const_value()?, rhs.const_value()?);
+                match op {
+                    BinaryOperator::Add => Some(lhs.wrapping_add(rhs)),
+                    BinaryOperator::Sub => Some(lhs.wrapping_sub(rhs)),
+                    BinaryOperator::Mul => Some(lhs.wrapping_mul(rhs)),
+                    // a division by zero is left to fail at run time
+                    BinaryOperator::Div => lhs.checked_div(rhs),
+                    BinaryOperator::Mod => lhs.checked_rem(rhs),
+                    BinaryOperator::And => Some((lhs != 0 && rhs != 0) as i64),
+                    BinaryOperator::Or => Some((lhs != 0 || rhs != 0) as i64),
+                    BinaryOperator::BitAnd => Some(lhs & rhs),
+                    BinaryOperator::BitOr => Some(lhs | rhs),
+                    BinaryOperator::Xor => Some(lhs ^ rhs),
+                    BinaryOperator::Shl => lhs.checked_shl(u32::try_from(rhs).ok()?),
+                    BinaryOperator::Shr => lhs.checked_shr(u32::try_from(rhs).ok()?),
+                    BinaryOperator::Eq => Some((lhs == rhs) as i64),
+                    BinaryOperator::Neq => Some((lhs != rhs) as i64),
+                    BinaryOperator::Lt => Some((lhs < rhs) as i64),
+                    BinaryOperator::Gt => Some((lhs > rhs) as i64),
+                    BinaryOperator::Leq => Some((lhs <= rhs) as i64),
+                    BinaryOperator::Geq => Some((lhs >= rhs) as i64),
+                    BinaryOperator::Assign => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    // whether evaluating the expression produces a value, an `if` only does when every arm
+    // that does not jump away does, and at least one arm is left
+    pub fn has_value(&self) -> bool {
+        match self {
+            Expr::CompoundExpr { value, .. } => value.is_some(),
+            Expr::If { then_branch, else_branch: Some(else_branch), .. } => {
+                Expr::arms_have_value([then_branch.as_ref(), else_branch.as_ref()])
+            }
+            Expr::If { else_branch: None, .. } => false,
+            // without a `default` no arm may run
+            Expr::Switch { cases, .. } => {
+                cases.iter().any(|case| case.is_default()) && Expr::arms_have_value(cases.iter().map(|case| case.body.as_ref()))
+            }
+            Expr::While { .. } | Expr::DoWhile { .. } | Expr::For { .. } => false,
+            Expr::Break { .. } | Expr::Continue { .. } | Expr::Return { .. } | Expr::Fallthrough { .. } => false,
+            _ => true,
+        }
+    }
+
+    fn arms_have_value<'a>(arms: impl IntoIterator<Item = &'a Expr> + Clone) -> bool {
+        arms.clone().into_iter().all(|arm| arm.has_value() || arm.diverges())
+            && arms.into_iter().any(|arm| arm.has_value())
+    }
+
+    // whether control never reaches the end of the expression,
+    // because every path returns, jumps away or loops forever
+    pub fn diverges(&self) -> bool {
+        match self {
+            Expr::Return { .. } | Expr::Break { .. } | Expr::Continue { .. } | Expr::Fallthrough { .. } => true,
+            Expr::CompoundExpr { expressions, .. } => expressions.iter().any(|decl| match decl {
+                DeclType::SideEffect(expr) => expr.diverges(),
+                _ => false,
+            }),
+            Expr::If { then_branch, else_branch: Some(else_branch), .. } => {
+                then_branch.diverges() && else_branch.diverges()
+            }
+            Expr::Switch { cases, .. } => {
+                cases.iter().any(|case| case.is_default()) && cases.iter().all(|case| case.body.diverges())
+            }
+            // a loop without a condition, or with a constant true one, only ends through a `break`
+            Expr::For { label, cond: None, body, .. } => !body.breaks_out_of(label, 0),
+            Expr::While { label, cond, body } if cond.const_value().is_some_and(|v| v != 0) => {
+                !body.breaks_out_of(label, 0)
+            }
+            _ => false,
+        }
+    }
+
+    // whether a `break` in the expression leaves the loop labeled `label`,
+    // `depth` counts the loops between the expression and that one
+    fn breaks_out_of(&self, label: &Option<LoopLabel>, depth: usize) -> bool {
+        let any = |exprs: &[&Expr], depth| exprs.iter().any(|expr| expr.breaks_out_of(label, depth));
+        match self {
+            Expr::Break { label: Some(target), .. } => label.as_ref().is_some_and(|l| l.name == target.name),
+            Expr::Break { label: None, .. } => depth == 0,
+            Expr::While { cond, body, .. } | Expr::DoWhile { cond, body, .. } => {
+                cond.breaks_out_of(label, depth) || body.breaks_out_of(label, depth + 1)
+            }
+            Expr::For { init, cond, step, body, .. } => {
+                init.as_ref().is_some_and(|init| init.breaks_out_of(label, depth))
+                    || cond.as_ref().is_some_and(|cond| cond.breaks_out_of(label, depth))
+                    || step.as_ref().is_some_and(|step| step.breaks_out_of(label, depth))
+                    || body.breaks_out_of(label, depth + 1)
+            }
+            Expr::BinaryOp { lhs, rhs, .. } => any(&[lhs, rhs], depth),
+            Expr::CompoundAssign { target, value, .. } => any(&[target, value], depth),
+            Expr::UnaryOp { expr, .. } => expr.breaks_out_of(label, depth),
+            Expr::Call { func, args } => func.breaks_out_of(label, depth) || args.iter().any(|arg| arg.breaks_out_of(label, depth)),
+            Expr::If { cond, then_branch, else_branch } => {
+                any(&[cond, then_branch], depth)
+                    || else_branch.as_ref().is_some_and(|branch| branch.breaks_out_of(label, depth))
+            }
+            Expr::Switch { scrutinee, cases, .. } => {
+                scrutinee.breaks_out_of(label, depth) || cases.iter().any(|case| case.body.breaks_out_of(label, depth))
+            }
+            Expr::Return { value: Some(value), .. } => value.breaks_out_of(label, depth),
+            Expr::CompoundExpr { expressions, .. } => expressions.iter().any(|decl| decl.breaks_out_of(label, depth)),
+            _ => false,
+        }
+    }
+}
+
+impl DeclType {
+    fn breaks_out_of(&self, label: &Option<LoopLabel>, depth: usize) -> bool {
+        match self {
+            DeclType::SideEffect(expr) => expr.breaks_out_of(label, depth),
+            DeclType::VariableDecl { init: Some(init), .. } => init.breaks_out_of(label, depth),
+            _ => false,
+        }
+    }
+}
+
+impl SwitchCase {
+    pub fn is_default(&self) -> bool {
+        self.values.is_empty()
+    }
 }
 
 impl Type {
@@ -163,7 +485,74 @@ impl Type {
         Box::new(Type::PointerType { to })
     }
 
+    pub fn new_ref(to: Box<Type>) -> Box<Self> {
+        Box::new(Type::RefType { to, mutable: false })
+    }
+
+    // the values an integer type can hold, None for non-integer types
+    pub fn integer_range(&self) -> Option<(i128, i128)> {
+        let (bits, signed) = match self {
+            Type::CxInteger { bits, signed } => (*bits as u32, *signed),
+            Type::Int => (64, true),
+            Type::Char => (8, false),
+            _ => return None,
+        };
+        if signed {
+            Some((-(1i128 << (bits - 1)), (1i128 << (bits - 1)) - 1))
+        } else {
+            Some((0, (1i128 << bits) - 1))
+        }
+    }
+
     pub fn is_integer(&self) -> bool {
         matches!(self, Type::CxInteger { .. } | Type::Int | Type::Char)
     }
+
+    pub fn is_unsigned(&self) -> bool {
+        matches!(self, Type::CxInteger { signed: false, .. } | Type::Char)
+    }
+
+    // the size of a value in bytes, everything but the narrow integers takes 8
+    pub fn size(&self) -> usize {
+        match self {
+            Type::CxInteger { bits, .. } => *bits as usize / 8,
+            Type::Char => 1,
+            _ => 8,
+        }
+    }
+}
+
+// types are shown the way they are written in Cx source
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Type::TypeName(name) => write!(f, "{}", name),
+            Type::CxInteger { bits, signed } => write!(f, "{}{}", if *signed { "i" } else { "u" }, bits),
+            Type::Int => write!(f, "int"),
+            Type::Void => write!(f, "void"),
+            Type::Char => write!(f, "char"),
+            Type::CompoundType { name, .. } => write!(f, "struct {}", name),
+            Type::PointerType { to } => write!(f, "*{}", to),
+            Type::RefType { to, mutable: true } => write!(f, "&mut {}", to),
+            Type::RefType { to, mutable: false } => write!(f, "&{}", to),
+            Type::ArrayType { of, length: Some(length) } => write!(f, "[{}; {}]", of, length),
+            Type::ArrayType { of, length: None } => write!(f, "[{}]", of),
+            Type::FunctionType { return_type, param_types, variadic, .. } => {
+                let mut params: Vec<String> = param_types.iter().map(|t| t.to_string()).collect();
+                if *variadic {
+                    params.push("...".to_string());
+                }
+                write!(f, "fn({}) -> {}", params.join(", "), return_type)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Mutability {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Mutability::Mutable => write!(f, "mutable"),
+            Mutability::Immutable => write!(f, "immutable"),
+        }
+    }
 }
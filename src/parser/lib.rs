@@ -1,74 +1,326 @@
-use super::token::{Token, TokenType, Span};
+use super::token::{FileId, IntSuffix, Token, TokenType, Span};
+use crate::diag::diag::Diag;
+
+// every operator, longest first so the first match is the maximal munch
+const OPERATORS: [(&str, TokenType); 35] = [
+    ("<<=", TokenType::ShlAssign), (">>=", TokenType::ShrAssign),
+    ("==", TokenType::Eq), ("!=", TokenType::Neq),
+    ("<=", TokenType::Leq), (">=", TokenType::Geq),
+    ("&&", TokenType::And), ("||", TokenType::Or),
+    ("++", TokenType::Inc), ("--", TokenType::Dec),
+    ("<<", TokenType::Shl), (">>", TokenType::Shr),
+    ("<-", TokenType::LArrow), ("->", TokenType::RArrow),
+    ("+=", TokenType::AddAssign), ("-=", TokenType::SubAssign),
+    ("*=", TokenType::MulAssign), ("/=", TokenType::DivAssign),
+    ("%=", TokenType::ModAssign), ("&=", TokenType::AndAssign),
+    ("|=", TokenType::OrAssign), ("^=", TokenType::XorAssign),
+    ("+", TokenType::Add), ("-", TokenType::Sub),
+    ("*", TokenType::Mul), ("/", TokenType::Div),
+    ("%", TokenType::Mod), ("=", TokenType::Assign),
+    ("<", TokenType::Lt), (">", TokenType::Gt),
+    ("!", TokenType::Not), ("~", TokenType::BitNot),
+    ("&", TokenType::BitAnd), ("|", TokenType::BitOr),
+    ("^", TokenType::Xor),
+];
+
+const INT_SUFFIXES: [(&str, IntSuffix); 8] = [
+    ("i8", IntSuffix { bits: 8, signed: true }), ("i16", IntSuffix { bits: 16, signed: true }),
+    ("i32", IntSuffix { bits: 32, signed: true }), ("i64", IntSuffix { bits: 64, signed: true }),
+    ("u8", IntSuffix { bits: 8, signed: false }), ("u16", IntSuffix { bits: 16, signed: false }),
+    ("u32", IntSuffix { bits: 32, signed: false }), ("u64", IntSuffix { bits: 64, signed: false }),
+];
 
 #[derive(Debug, Clone)]
 pub struct Lexer {
     source: String,
-    line:   usize,
+    // the file `source` was loaded as, stamped on every span
+    file:     FileId,
+    // byte offset into `source`
     position: usize,
-    column:    usize,
-    pline:    usize,
-    pcol:     usize
+    // where the token being lexed starts
+    start:    usize,
+    errors:   Vec<Diag>,
 }
 
 impl Lexer {
-    pub fn new(source: String) -> Self {
-        Lexer { source, line: 1, pline: 1, column: 0, pcol: 0, position: 0}
+    pub fn new(source: String, file: FileId) -> Self {
+        Lexer { source, file, position: 0, start: 0, errors: Vec::new() }
     }
 
     fn peek(&self) -> Option<char> {
-        self.source.chars().nth(self.position)
+        self.source[self.position..].chars().next()
+    }
+
+    // only ever used with small `n`, to look at the rest of an operator
+    fn peek_nth(&self, n: usize) -> Option<char> {
+        self.source[self.position..].chars().nth(n)
     }
 
     fn eat(&mut self) -> Option<char> {
         let ch = self.peek()?;
-        self.position += 1;
-        if ch == '\n' {
-            self.line += 1;
-            self.column = 0;
-        } else {
-            self.column += 1;
-        }
+        self.position += ch.len_utf8();
         Some(ch)
     }
     
     fn snap_shot(&mut self) {
-        self.pline = self.line;
-        self.pcol = self.column;
+        self.start = self.position;
+    }
+
+    // the span from the last snapshot up to the current position
+    fn current_span(&self) -> Span {
+        Span::new(self.file, self.start, self.position)
     }
 
     fn make_token(&mut self, token_type: TokenType) -> Token {
-        let span = Span::new(
-            self.pline,
-            self.line,
-            self.pcol,
-            self.column - 1
-        );
-        Token::new(token_type, span)
+        Token::new(token_type, self.current_span())
     }
 
-    pub fn next_token(&mut self) -> Token {
-        self.snap_shot();
+    // lexes the whole source, the returned tokens always end with an `Eof`.
+    // lexical errors do not stop lexing, they are returned alongside the tokens
+    pub fn tokenize(mut self) -> (Vec<Token>, Vec<Diag>) {
+        let mut tokens = Vec::new();
+        loop {
+            let token = self.next_token();
+            let done = token.matches(&TokenType::Eof);
+            tokens.push(token);
+            if done {
+                return (tokens, self.errors);
+            }
+        }
+    }
 
-        let Some(ch) = self.peek() else {
-            return self.make_token(TokenType::Eof);
+    // lexes the escape sequence after a '\\' into the byte it stands for
+    fn lex_escape(&mut self) -> Result<u8, Diag> {
+        // errors point at the escape, not at the whole literal
+        let start = self.position - 1;
+        let escape_span = |lexer: &Self| Span::new(lexer.file, start, lexer.position);
+        let Some(ch) = self.eat() else {
+            return Err(Diag::InvalidEscape("\\".to_string(), escape_span(self)));
         };
-        
-        return match ch {
-            '0'..='9' => {
-                let mut num = 0i64;
-                while let Some(digit) = self.peek().filter(|c| c.is_ascii_digit()) {
-                    num = num * 10 + (digit as i64 - '0' as i64);
+        let byte = match ch {
+            'n' => b'\n',
+            't' => b'\t',
+            'r' => b'\r',
+            '0' => b'\0',
+            '\\' => b'\\',
+            '"' => b'"',
+            '\'' => b'\'',
+            'x' => {
+                let mut digits = String::new();
+                while digits.len() < 2 {
+                    match self.peek().filter(|c| c.is_ascii_hexdigit()) {
+                        Some(c) => { digits.push(c); self.eat(); }
+                        None => break,
+                    }
+                }
+                if digits.len() != 2 {
+                    return Err(Diag::InvalidEscape(format!("\\x{}", digits), escape_span(self)));
+                }
+                u8::from_str_radix(&digits, 16).unwrap()
+            }
+            _ => return Err(Diag::InvalidEscape(format!("\\{}", ch), escape_span(self))),
+        };
+        Ok(byte)
+    }
+
+    fn lex_string(&mut self) -> Token {
+        self.eat(); // eat '"'
+        let mut bytes = Vec::new();
+        loop {
+            match self.peek() {
+                Some('"') => {
                     self.eat();
+                    break;
                 }
-                self.make_token(TokenType::IntegerLiteral(num))
-            },
+                // strings may not span lines
+                None | Some('\n') => {
+                    self.errors.push(Diag::UnterminatedString(self.current_span()));
+                    break;
+                }
+                Some('\\') => {
+                    self.eat();
+                    match self.lex_escape() {
+                        Ok(byte) => bytes.push(byte),
+                        Err(e) => self.errors.push(e),
+                    }
+                }
+                Some(c) => {
+                    self.eat();
+                    let mut buf = [0; 4];
+                    bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                }
+            }
+        }
+        self.make_token(TokenType::StringLiteral(bytes))
+    }
+
+    // decimal, 0x hex, 0o octal and 0b binary integers, digits may be separated by '_'
+    // and followed by a type suffix such as `u8`.
+    // whether the value fits the suffix is up to the parser, which knows about negation
+    fn lex_number(&mut self) -> Token {
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == '_') {
+            self.eat();
+        }
+        let text = self.source[start..self.position].to_string();
+
+        // i and u are not digits in any radix, so the suffix can be split off first
+        let (number, suffix) = match INT_SUFFIXES.iter().find(|(name, _)| text.ends_with(name)) {
+            Some((name, suffix)) => (&text[..text.len() - name.len()], Some(*suffix)),
+            None => (text.as_str(), None),
+        };
+
+        let (radix, digits) = match number.get(..2) {
+            Some("0x") | Some("0X") => (16, &number[2..]),
+            Some("0o") | Some("0O") => (8, &number[2..]),
+            Some("0b") | Some("0B") => (2, &number[2..]),
+            _ => (10, number),
+        };
+
+        let mut value: Option<u64> = Some(0);
+        let mut seen_digit = false;
+        for c in digits.chars().filter(|c| *c != '_') {
+            let Some(digit) = c.to_digit(radix) else {
+                value = None;
+                break;
+            };
+            seen_digit = true;
+            value = value
+                .and_then(|v| v.checked_mul(radix as u64))
+                .and_then(|v| v.checked_add(digit as u64));
+        }
+
+        match value.filter(|_| seen_digit) {
+            Some(value) => self.make_token(TokenType::IntegerLiteral(value, suffix)),
+            None => {
+                self.errors.push(Diag::InvalidNumberFormat(text, self.current_span()));
+                self.make_token(TokenType::IntegerLiteral(0, suffix))
+            }
+        }
+    }
+
+    fn lex_operator(&mut self) -> Option<Token> {
+        let (op, token_type) = OPERATORS.iter().find(|(op, _)| {
+            op.chars().enumerate().all(|(i, c)| self.peek_nth(i) == Some(c))
+        })?;
+        for _ in 0..op.len() {
+            self.eat();
+        }
+        Some(self.make_token(token_type.clone()))
+    }
+
+    // skips to the end of the line, the leading '/' is already eaten
+    fn skip_line_comment(&mut self) {
+        while let Some(c) = self.peek() {
+            if c == '\n' {
+                break;
+            }
+            self.eat();
+        }
+    }
+
+    // skips a (possibly nested) block comment, the leading '/' is already eaten
+    fn skip_block_comment(&mut self) {
+        self.eat(); // eat '*'
+        let opening = self.current_span();
+        let mut depth = 1;
+        while depth > 0 {
+            let Some(c) = self.eat() else {
+                self.errors.push(Diag::UnterminatedComment(opening));
+                return;
+            };
+            match (c, self.peek()) {
+                ('/', Some('*')) => { self.eat(); depth += 1; }
+                ('*', Some('/')) => { self.eat(); depth -= 1; }
+                _ => {}
+            }
+        }
+    }
+
+    // `'name` without a closing '\'' is a loop label, `'a'` stays a character literal
+    fn at_label(&self) -> bool {
+        let rest = &self.source[self.position + 1..];
+        let len = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(rest.len());
+        len > 0 && !rest.starts_with(|c: char| c.is_ascii_digit()) && !rest[len..].starts_with('\'')
+    }
+
+    fn lex_label(&mut self) -> Token {
+        self.eat(); // eat '\''
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == '_') {
+            self.eat();
+        }
+        let name = self.source[start..self.position].to_string();
+        self.make_token(TokenType::Label(name))
+    }
+
+    fn lex_char(&mut self) -> Token {
+        self.eat(); // eat '\''
+        // chars are single bytes, so only ascii characters can be written directly
+        let mut bytes = Vec::new();
+        let mut non_ascii = false;
+        let mut terminated = false;
+        while let Some(c) = self.peek() {
+            match c {
+                '\'' => {
+                    self.eat();
+                    terminated = true;
+                    break;
+                }
+                '\n' => break,
+                '\\' => {
+                    self.eat();
+                    match self.lex_escape() {
+                        Ok(byte) => bytes.push(byte),
+                        Err(e) => { self.errors.push(e); bytes.push(0); }
+                    }
+                }
+                _ => {
+                    self.eat();
+                    non_ascii |= !c.is_ascii();
+                    bytes.push(if c.is_ascii() { c as u8 } else { 0 });
+                }
+            }
+        }
+
+        if !terminated {
+            self.errors.push(Diag::UnterminatedChar(self.current_span()));
+        } else if bytes.is_empty() {
+            self.errors.push(Diag::EmptyCharLiteral(self.current_span()));
+        } else if non_ascii {
+            self.errors.push(Diag::NonAsciiCharLiteral(self.current_span()));
+        } else if bytes.len() > 1 {
+            self.errors.push(Diag::MultiCharLiteral(self.current_span()));
+        }
+        let value = bytes.first().copied().unwrap_or(0);
+        self.make_token(TokenType::CharLiteral(value))
+    }
+
+    pub fn next_token(&mut self) -> Token {
+        // trivia is skipped in a loop rather than by recursing, long runs of it would overflow the stack
+        loop {
+            self.snap_shot();
+
+            let Some(ch) = self.peek() else {
+                return self.make_token(TokenType::Eof);
+            };
+            if let Some(token) = self.lex_token(ch) {
+                return token;
+            }
+        }
+    }
+
+    // lexes the token starting with `ch`, None if `ch` started trivia (or garbage) that was skipped
+    fn lex_token(&mut self, ch: char) -> Option<Token> {
+        let token = match ch {
+            '0'..='9' => self.lex_number(),
             'a'..='z' | 'A'..='Z' | '_' => {
-                let mut ident = String::new();
-                while let Some(c) = self.peek().filter(|c| c.is_ascii_alphanumeric() || *c == '_') {
-                    ident.push(c);
+                let start = self.position;
+                while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == '_') {
                     self.eat();
                 }
-                let token_type = match ident.as_str() {
+                let ident = &self.source[start..self.position];
+                let token_type = match ident {
                     "fn" => TokenType::Fn,
                     "var" => TokenType::Var,
                     "int" => TokenType::Int,
@@ -91,39 +343,60 @@ impl Lexer {
                     "switch" => TokenType::Switch,
                     "case" => TokenType::Case,
                     "default" => TokenType::Default,
+                    "fallthrough" => TokenType::Fallthrough,
                     "do" => TokenType::Do,
                     "goto" => TokenType::Goto,
                     "union" => TokenType::Union,
-                    _ => TokenType::Identifier(ident),
+                    "i8" => TokenType::I8,
+                    "i16" => TokenType::I16,
+                    "i32" => TokenType::I32,
+                    "i64" => TokenType::I64,
+                    "u8" => TokenType::U8,
+                    "u16" => TokenType::U16,
+                    "u32" => TokenType::U32,
+                    "u64" => TokenType::U64,
+                    _ => TokenType::Identifier(ident.to_string()),
                 };
                 self.make_token(token_type)
             },
-            '+' => { self.eat(); self.make_token(TokenType::Add) },
-            '-' => { self.eat(); 
-                if let Some('>') = self.peek() {
-                    self.eat();
-                    self.make_token(TokenType::RArrow)
-                } else { 
-                    self.make_token(TokenType::Sub) 
-                } 
+            '"' => self.lex_string(),
+            '\'' if self.at_label() => self.lex_label(),
+            '\'' => self.lex_char(),
+            '/' if matches!(self.peek_nth(1), Some('/') | Some('*')) => {
+                self.eat();
+                if self.peek() == Some('/') {
+                    self.skip_line_comment();
+                } else {
+                    self.skip_block_comment();
+                }
+                return None;
             },
-            '*' => { self.eat(); self.make_token(TokenType::Mul) },
-            '/' => { self.eat(); self.make_token(TokenType::Div) },
             '(' => { self.eat(); self.make_token(TokenType::LParen) },
             ')' => { self.eat(); self.make_token(TokenType::RParen) },
             '{' => { self.eat(); self.make_token(TokenType::LBrace) },
             '}' => { self.eat(); self.make_token(TokenType::RBrace) },
             ';' => { self.eat(); self.make_token(TokenType::SemiColon) },
+            ':' if self.peek_nth(1) == Some(':') => {
+                self.eat();
+                self.eat();
+                self.make_token(TokenType::ColonColon)
+            },
             ':' => { self.eat(); self.make_token(TokenType::Colon) },
             ',' => { self.eat(); self.make_token(TokenType::Comma) },
-            '=' => { self.eat(); self.make_token(TokenType::Eq) },
             c if c.is_whitespace() => {
                 self.eat();
-                self.next_token()
+                return None;
             },
-            _ => {
-                panic!("Unexpected character: {}", ch);
+            _ => match self.lex_operator() {
+                Some(token) => token,
+                None => {
+                    // report and skip it, the parser never sees the character
+                    self.eat();
+                    self.errors.push(Diag::InvalidCharacter(ch, self.current_span()));
+                    return None;
+                }
             }
-        }
+        };
+        Some(token)
     }
 }
@@ -1,4 +1,6 @@
 pub mod lib;
+#[allow(clippy::module_inception)]
 pub mod parser;
 pub mod ast;
 pub mod token;
+pub mod source_map;
@@ -16,8 +16,12 @@ pub struct Parser {
     position: usize,
     // a program is a list of declarations
     program: Vec<parser::ast::DeclType>,
-    // current parsing context
+    // current parsing context, decides where to resume after an error
     context: ParseContext,
+    // errors that were recovered from
+    errors: Vec<Diag>,
+    // the enums declared so far, `Name::Variant` is replaced by its value
+    enums: parser::ast::Enums,
 }
 
 impl Parser {
@@ -27,15 +31,43 @@ impl Parser {
             position: 0,
             program: Vec::new(),
             context: ParseContext::Global,
+            errors: Vec::new(),
+            enums: parser::ast::Enums::new(),
         }
     }
 
+    // starts out knowing the enums of the files parsed before
+    pub fn with_enums(mut self, enums: parser::ast::Enums) -> Self {
+        self.enums = enums;
+        self
+    }
+
+    // the enums known after parsing, for the files parsed next
+    pub fn take_enums(&mut self) -> parser::ast::Enums {
+        std::mem::take(&mut self.enums)
+    }
+
     fn set_context(&mut self, ctx: ParseContext) -> ParseContext {
         let old_ctx = self.context;
         self.context = ctx;
         old_ctx
     }
 
+    // runs `f` in `ctx`, the previous context is restored even if `f` fails
+    fn in_context<T, F>(&mut self, ctx: ParseContext, f: F) -> T
+    where
+        F: FnOnce(&mut Self) -> T,
+    {
+        let old_ctx = self.set_context(ctx);
+        let result = f(self);
+        self.set_context(old_ctx);
+        result
+    }
+
+    fn peek_is(&self, token_type: &parser::token::TokenType) -> bool {
+        self.peek().is_some_and(|tok| tok.matches(token_type))
+    }
+
     fn peek(&self) -> Option<&parser::token::Token> {
         self.token_buffer.get(self.position)
     }
@@ -44,83 +76,173 @@ impl Parser {
         self.position += 1;
     }
 
-    fn recover_to(&mut self, token_type: parser::token::TokenType) {
-        loop {
-            let tok = self.peek();
-            if (tok.unwrap().matches(&token_type)) || tok.unwrap().matches(&parser::token::TokenType::Eof) {
-                break;
+    // skips to where parsing can resume in the current context:
+    // * Global, Function: before the next `fn`, `var` or `enum`, or past a `;` or a closing `}`
+    // * Block: before the next `var` or the closing `}`, or past a `;`
+    // * FunctionParams: before the next `,` or `)`, stopping at the start of the body
+    // * Expression: before the next `,` or `)`, stopping at the end of the statement
+    // brackets opened while skipping are skipped as a whole
+    fn auto_recover(&mut self) {
+        let mut depth = 0usize;
+        while let Some(tok) = self.peek() {
+            let token_type = tok.get_type();
+            if matches!(token_type, parser::token::TokenType::Eof) {
+                return;
+            }
+            if depth == 0 {
+                let (stop_before, stop_after) = match self.context {
+                    ParseContext::Global | ParseContext::Function => (
+                        matches!(token_type, parser::token::TokenType::Fn | parser::token::TokenType::Var | parser::token::TokenType::Enum),
+                        matches!(token_type, parser::token::TokenType::SemiColon | parser::token::TokenType::RBrace),
+                    ),
+                    ParseContext::Block => (
+                        matches!(token_type, parser::token::TokenType::Var | parser::token::TokenType::RBrace),
+                        matches!(token_type, parser::token::TokenType::SemiColon),
+                    ),
+                    ParseContext::FunctionParams => (
+                        matches!(token_type, parser::token::TokenType::Comma | parser::token::TokenType::RParen
+                            | parser::token::TokenType::LBrace | parser::token::TokenType::RArrow
+                            | parser::token::TokenType::SemiColon | parser::token::TokenType::RBrace),
+                        false,
+                    ),
+                    ParseContext::Expression => (
+                        matches!(token_type, parser::token::TokenType::Comma | parser::token::TokenType::RParen
+                            | parser::token::TokenType::SemiColon | parser::token::TokenType::RBrace),
+                        false,
+                    ),
+                };
+                if stop_before {
+                    return;
+                }
+                if stop_after {
+                    self.eat();
+                    return;
+                }
+            }
+            match token_type {
+                parser::token::TokenType::LParen | parser::token::TokenType::LBrace => depth += 1,
+                parser::token::TokenType::RParen | parser::token::TokenType::RBrace => depth = depth.saturating_sub(1),
+                _ => {}
             }
             self.eat();
         }
     }
 
-    fn auto_recover(&mut self) {
-        let anchor = match self.context {
-            ParseContext::Global | ParseContext::Block => parser::token::TokenType::SemiColon,
-            _ => unreachable!()
-        };
-        self.recover_to(anchor);
+    fn report(&mut self, error: Diag) {
+        // once the file has ended every open construct would report it again
+        if matches!(error, Diag::EarlyEOF(_)) && self.errors.iter().any(|e| matches!(e, Diag::EarlyEOF(_))) {
+            return;
+        }
+        self.errors.push(error);
+    }
+
+    // records `error` and skips to where the current context can go on
+    fn recover(&mut self, error: Diag) {
+        self.report(error);
+        self.auto_recover();
     }
 
     fn expect_identifier(&mut self) -> Result<String, Diag> {
-        if let Some(token) = self.peek() {
-            if token.is_an_identifier() {
-                if let parser::token::TokenType::Identifier(name) = token.get_type() {
-                    let name_clone = name.clone();
-                    self.eat();
-                    return Ok(name_clone);
-                } else {
-                    unreachable!();
-                }
+        if let Some(token) = self.peek()
+            && token.is_an_identifier() {
+            if let parser::token::TokenType::Identifier(name) = token.get_type() {
+                let name_clone = name.clone();
+                self.eat();
+                return Ok(name_clone);
+            } else {
+                unreachable!();
             }
         }
         Err(Diag::MissingIdentifier(self.current_span().clone()))
     }
 
-    fn expect(&mut self, expected: parser::token::TokenType) -> bool {
-        if let Some(token) = self.peek() {
-            if token.matches(&expected) {
-                self.eat();
-                return true;
-            }
+    // the name a declaration introduces, `follow` is the token written after it.
+    // finding that token right away means the name was left out
+    fn expect_declared_name(&mut self, follow: parser::token::TokenType) -> Result<String, Diag> {
+        if self.peek_is(&follow) {
+            return Err(Diag::DeclarationMissingAName(self.end_of_previous()));
         }
-        false
+        self.expect_identifier()
+    }
+
+    // eats `expected`, a missing token is reported right after the previous one
+    fn expect(&mut self, expected: parser::token::TokenType) -> Result<(), Diag> {
+        if let Some(token) = self.peek()
+            && token.matches(&expected) {
+            self.eat();
+            return Ok(());
+        }
+        Err(Diag::MissingToken(expected.display(), self.end_of_previous()))
     }
 
+    // the span of the offending token, the final `Eof` once everything is consumed
     fn current_span(&self) -> &parser::token::Span {
-       self.token_buffer[0].get_span()
+        let token = self.peek().or(self.token_buffer.last()).expect("token buffer always ends with Eof");
+        token.get_span()
+    }
+
+    // the empty span just past the last consumed token, where a missing token belongs
+    fn end_of_previous(&self) -> parser::token::Span {
+        if self.position == 0 {
+            let start = self.current_span();
+            return parser::token::Span::new(start.file(), start.start(), start.start());
+        }
+        let prev = self.token_buffer[self.position - 1].get_span();
+        parser::token::Span::new(prev.file(), prev.end(), prev.end())
+    }
+
+    // the span of the next token, where a construct starting now begins
+    fn peek_span(&self) -> Option<parser::token::Span> {
+        self.peek().map(|tok| tok.get_span().clone())
+    }
+
+    // the span from `start` to the end of the last consumed token
+    fn span_from(&self, start: &Option<parser::token::Span>) -> parser::token::Span {
+        let last = self.token_buffer[self.position.saturating_sub(1)].get_span();
+        match start {
+            Some(start) => start.merge(last),
+            None => last.clone(),
+        }
     }
 
     fn match_and<F>(&mut self, expected: parser::token::TokenType, f: F) -> bool
     where
         F: Fn(&parser::token::Token) -> bool,
     {
-        if let Some(token) = self.peek() {
-            if token.matches(&expected) && f(token) {
-                self.eat();
-                return true;
-            }
+        if let Some(token) = self.peek()
+            && token.matches(&expected) && f(token) {
+            self.eat();
+            return true;
         }
         false
     }
 
     pub fn parse_program(&mut self) -> Result<Vec<parser::ast::DeclType>, Vec<Diag>> {
-        let mut errors = Vec::new();
         while let Some(token) = self.peek() {
             if token.matches(&parser::token::TokenType::Eof) {
                 break;
             }
+            let start = self.position;
+            let start_span = self.peek_span();
             match self.parse_declaration() {
+                // there is no code outside of functions that could run it
+                Ok(parser::ast::DeclType::SideEffect(_)) => {
+                    let span = self.span_from(&start_span);
+                    self.report(Diag::ExpressionOutsideFunction(span));
+                }
                 Ok(decl) => self.program.push(decl),
                 Err(e) => {
-                    self.auto_recover();
-                    errors.push(e);
+                    self.recover(e);
+                    // always make progress, or the same error is reported forever
+                    if self.position == start {
+                        self.eat();
+                    }
                 }
             }
         }
 
-        if !errors.is_empty() {
-            return Err(errors);
+        if !self.errors.is_empty() {
+            return Err(std::mem::take(&mut self.errors));
         }
         
         Ok(std::mem::take(&mut self.program))
@@ -139,44 +261,63 @@ impl Parser {
         
         match tok.get_type() {
             parser::token::TokenType::Fn => {
-                return self.parse_function_decl();
+                let keyword = tok.get_span().clone();
+                let decl = self.parse_function_decl()?;
+                // functions only live at the top level, they could not reach the locals around them
+                if self.context != ParseContext::Global {
+                    self.report(Diag::NestedFunction(keyword));
+                }
+                Ok(decl)
             }
             parser::token::TokenType::Var => {
-                return self.parse_variable_decl();
+                self.parse_variable_decl()
+            }
+            parser::token::TokenType::Enum if self.context == ParseContext::Global => {
+                self.parse_enum_decl()
+            }
+            // a statement starting with a block ends with it, `if c { .. } *p = 1` is not a product
+            parser::token::TokenType::If | parser::token::TokenType::LBrace
+            | parser::token::TokenType::While | parser::token::TokenType::For
+            | parser::token::TokenType::Label(_) | parser::token::TokenType::Switch => {
+                let expr = self.parse_primary()?;
+                self.match_and(parser::token::TokenType::SemiColon, |_| true);
+                Ok(parser::ast::DeclType::SideEffect(*expr))
             }
             _ => {
                 let expr = self.parse_expression()?;
-                return Ok(parser::ast::DeclType::SideEffect(*expr));
+                // only the last expression of a block may leave out the ';', it is the block's value
+                if !self.match_and(parser::token::TokenType::SemiColon, |_| true)
+                    && !self.peek_is(&parser::token::TokenType::RBrace) && !self.peek_is(&parser::token::TokenType::Eof) {
+                    self.report(Diag::MissingToken(";".to_string(), self.end_of_previous()));
+                }
+                Ok(parser::ast::DeclType::SideEffect(*expr))
             } 
         }
     }
 
     fn parse_function_decl(&mut self) -> Result<parser::ast::DeclType, Diag> {
         self.eat();  // eat 'fn'
-        let function_name = self.expect_identifier()?;
-
-        self.expect(parser::token::TokenType::LParen);
+        let (function_name, params, function_type) = self.in_context(ParseContext::Function, |p| {
+            let function_name = p.expect_declared_name(parser::token::TokenType::LParen)?;
+            p.expect(parser::token::TokenType::LParen)?;
+            let params = p.in_context(ParseContext::FunctionParams, |p| {
+                let terminators = [
+                    parser::token::TokenType::LBrace,
+                    parser::token::TokenType::RArrow,
+                    parser::token::TokenType::SemiColon,
+                    parser::token::TokenType::RBrace,
+                ];
+                p.parse_list(&terminators, |p| p.parse_parameter())
+            });
 
-        let mut params = vec![];
-        while !self.match_and(parser::token::TokenType::RParen, |_| true) {
-            let param_name = self.expect_identifier()?;
-            self.expect(parser::token::TokenType::Colon); 
-            let param_type = self.parse_type()?;
-            params.push(parser::ast::Parameter::new_named(param_name, param_type, parser::ast::TakeType::ByValue));
-            if self.match_and(parser::token::TokenType::Comma, |_| true) {
-                self.eat();
+            let mut function_type = parser::ast::Type::new_void();
+            if p.match_and(parser::token::TokenType::RArrow, |_| true) {
+                function_type = p.parse_type()?;
             }
-        }
-
-        self.expect(parser::token::TokenType::RParen);
-        let mut function_type = parser::ast::Type::new_void();
-        if self.match_and(parser::token::TokenType::RArrow, |_| true) {
-            self.eat();
-            function_type = self.parse_type()?;
-        }
+            Ok((function_name, params, function_type))
+        })?;
 
         if self.match_and(parser::token::TokenType::SemiColon, |_| true) {
-            self.eat();
             return Ok(parser::ast::DeclType::FunctionDecl { 
                 name: function_name,
                 func_type: function_type,
@@ -190,37 +331,183 @@ impl Parser {
             function_type, params, body: Some(function_body) })
     }
 
+    // a `,` separated list up to and including the `)`, the `(` is already eaten.
+    // an error in one element does not stop the others, a token in `terminators`
+    // ends the list early when its `)` is missing
+    fn parse_list<T, F>(&mut self, terminators: &[parser::token::TokenType], mut parse_element: F) -> Vec<T>
+    where
+        F: FnMut(&mut Self) -> Result<T, Diag>,
+    {
+        let mut elements = vec![];
+        loop {
+            if self.match_and(parser::token::TokenType::RParen, |_| true) {
+                return elements;
+            }
+            let failed = match parse_element(self) {
+                Ok(element) => {
+                    elements.push(element);
+                    false
+                }
+                Err(e) => {
+                    self.recover(e);
+                    true
+                }
+            };
+            if self.match_and(parser::token::TokenType::Comma, |_| true) {
+                continue;
+            }
+            if self.match_and(parser::token::TokenType::RParen, |_| true) {
+                return elements;
+            }
+            // recovery already stopped at the end of the list
+            if failed {
+                return elements;
+            }
+            if self.peek_is(&parser::token::TokenType::Eof) || terminators.iter().any(|t| self.peek_is(t)) {
+                self.report(Diag::MissingToken(")".to_string(), self.end_of_previous()));
+                return elements;
+            }
+            // two elements without a `,` between them
+            self.recover(Diag::MissingToken(",".to_string(), self.end_of_previous()));
+            if self.match_and(parser::token::TokenType::Comma, |_| true) {
+                continue;
+            }
+            self.match_and(parser::token::TokenType::RParen, |_| true);
+            return elements;
+        }
+    }
+
+    fn parse_parameter(&mut self) -> Result<parser::ast::Parameter, Diag> {
+        let param_name = self.expect_identifier()?;
+        self.expect(parser::token::TokenType::Colon)?;
+        let param_type = self.parse_type()?;
+        Ok(parser::ast::Parameter::new_named(param_name, param_type, parser::ast::TakeType::ByValue))
+    }
+
     fn parse_variable_decl(&mut self) -> Result<parser::ast::DeclType, Diag> {
         let is_const = if let Some(tok) = self.peek() {
-            if *tok.get_type() == TokenType::Const {
-                true
-            } else {
-                false
-            }
+            *tok.get_type() == TokenType::Const
         } else {
             return Err(Diag::EarlyEOF(self.current_span().clone()))
         };
         self.eat();
 
-        let variable_name = self.expect_identifier()?;
+        let name_span = self.peek_span();
+        let variable_name = self.expect_declared_name(TokenType::Colon)?;
+        let name_span = self.span_from(&name_span);
         // types are a must for now 
-        self.expect(TokenType::Colon);
+        self.expect(TokenType::Colon)?;
         let variable_type = self.parse_type()?;
-        self.expect(TokenType::Eq);
-        let initializer = self.parse_expression()?;
+        let initializer = if self.match_and(TokenType::Assign, |_| true) {
+            Some(self.parse_expression()?)
+        } else {
+            None
+        };
 
-        self.expect(TokenType::SemiColon);
+        self.expect(TokenType::SemiColon)?;
         Ok(parser::ast::DeclType::VariableDecl {
             name: variable_name,
             var_type: variable_type,
-            init: Some(initializer),
+            init: initializer,
             mutability: if is_const { parser::ast::Mutability::Immutable } else { parser::ast::Mutability::Mutable },
+            span: name_span,
         })
     }
 
+    // `enum Name { A, B = 4, C }`, a variant without a value is one more than the one before it
+    fn parse_enum_decl(&mut self) -> Result<parser::ast::DeclType, Diag> {
+        self.eat();
+        let name_span = self.peek_span();
+        let name = self.expect_declared_name(TokenType::LBrace)?;
+        let span = self.span_from(&name_span);
+        self.expect(TokenType::LBrace)?;
+
+        let mut variants: Vec<parser::ast::EnumVariant> = Vec::new();
+        let mut next = 0i64;
+        while !self.match_and(TokenType::RBrace, |_| true) {
+            let variant_span = self.peek_span();
+            let variant = self.expect_identifier()?;
+            let variant_span = self.span_from(&variant_span);
+            // values are written like the values of a `case`
+            let value = if self.match_and(TokenType::Assign, |_| true) {
+                self.parse_case_value()?.value
+            } else {
+                next
+            };
+            next = value.wrapping_add(1);
+            if let Some(first) = variants.iter().find(|v| v.name == variant) {
+                self.report(Diag::RedefinedVariable {
+                    err_loc: variant_span.clone(),
+                    var_name: format!("{}::{}", name, variant),
+                    prev_decl: first.span.clone(),
+                });
+            }
+            variants.push(parser::ast::EnumVariant { name: variant, value, span: variant_span });
+            if !self.match_and(TokenType::Comma, |_| true) {
+                self.expect(TokenType::RBrace)?;
+                break;
+            }
+        }
+
+        if let Some(first) = self.enums.declare(&name, &span, &variants) {
+            self.report(Diag::RedefinedVariable { err_loc: span.clone(), var_name: name.clone(), prev_decl: first });
+        }
+        Ok(parser::ast::DeclType::EnumDecl { name, variants, span })
+    }
+
+    // `Name::Variant` after `Name` was read, starting at `start`
+    fn parse_variant(&mut self, name: String, start: &Option<parser::token::Span>) -> Result<Box<parser::ast::Expr>, Diag> {
+        self.expect(TokenType::ColonColon)?;
+        let variant = self.expect_identifier()?;
+        let span = self.span_from(start);
+        let Some(value) = self.enums.value(&name, &variant) else {
+            return Err(Diag::UndefinedVariable { err_loc: span, var_name: format!("{}::{}", name, variant) });
+        };
+        Ok(Box::new(parser::ast::Expr::IntegerLiteral { value, ty: Box::new(parser::ast::Type::TypeName(name)) }))
+    }
+
     // the classic RD expression chain
     fn parse_expression(&mut self) -> Result<Box<parser::ast::Expr>, Diag> {
-        self.parse_logical_or()
+        self.parse_assignment()
+    }
+
+    // assignments bind the loosest and group to the right: a = b = c is a = (b = c)
+    fn parse_assignment(&mut self) -> Result<Box<parser::ast::Expr>, Diag> {
+        let start = self.peek_span();
+        let target = self.parse_logical_or()?;
+        let Some(tok) = self.peek() else {
+            return Ok(target);
+        };
+
+        let op = match tok.get_type() {
+            TokenType::Assign => None,
+            TokenType::AddAssign => Some(parser::ast::BinaryOperator::Add),
+            TokenType::SubAssign => Some(parser::ast::BinaryOperator::Sub),
+            TokenType::MulAssign => Some(parser::ast::BinaryOperator::Mul),
+            TokenType::DivAssign => Some(parser::ast::BinaryOperator::Div),
+            TokenType::ModAssign => Some(parser::ast::BinaryOperator::Mod),
+            TokenType::AndAssign => Some(parser::ast::BinaryOperator::BitAnd),
+            TokenType::OrAssign => Some(parser::ast::BinaryOperator::BitOr),
+            TokenType::XorAssign => Some(parser::ast::BinaryOperator::Xor),
+            TokenType::ShlAssign => Some(parser::ast::BinaryOperator::Shl),
+            TokenType::ShrAssign => Some(parser::ast::BinaryOperator::Shr),
+            _ => return Ok(target),
+        };
+        let operator = tok.display();
+        if !target.is_lvalue() {
+            return Err(Diag::NotAnLvalue(operator, self.span_from(&start)));
+        }
+        self.eat();
+
+        let value = self.parse_assignment()?;
+        Ok(Box::new(match op {
+            None => parser::ast::Expr::BinaryOp {
+                op: parser::ast::BinaryOperator::Assign,
+                lhs: target,
+                rhs: value,
+            },
+            Some(op) => parser::ast::Expr::CompoundAssign { op, target, value },
+        }))
     }
 
     fn parse_logical_or(&mut self) -> Result<Box<parser::ast::Expr>, Diag> {
@@ -237,9 +524,9 @@ impl Parser {
     }
 
     fn parse_logical_and(&mut self) -> Result<Box<parser::ast::Expr>, Diag> {
-        let mut left = self.parse_equality()?;
+        let mut left = self.parse_bit_or()?;
         while self.match_and(parser::token::TokenType::And, |_| true) {
-            let right = self.parse_equality()?;
+            let right = self.parse_bit_or()?;
             left = Box::new(parser::ast::Expr::BinaryOp {
                 op: parser::ast::BinaryOperator::And,
                 lhs: left,
@@ -249,6 +536,45 @@ impl Parser {
         Ok(left)
     }
 
+    fn parse_bit_or(&mut self) -> Result<Box<parser::ast::Expr>, Diag> {
+        let mut left = self.parse_bit_xor()?;
+        while self.match_and(parser::token::TokenType::BitOr, |_| true) {
+            let right = self.parse_bit_xor()?;
+            left = Box::new(parser::ast::Expr::BinaryOp {
+                op: parser::ast::BinaryOperator::BitOr,
+                lhs: left,
+                rhs: right,
+            });
+        }
+        Ok(left)
+    }
+
+    fn parse_bit_xor(&mut self) -> Result<Box<parser::ast::Expr>, Diag> {
+        let mut left = self.parse_bit_and()?;
+        while self.match_and(parser::token::TokenType::Xor, |_| true) {
+            let right = self.parse_bit_and()?;
+            left = Box::new(parser::ast::Expr::BinaryOp {
+                op: parser::ast::BinaryOperator::Xor,
+                lhs: left,
+                rhs: right,
+            });
+        }
+        Ok(left)
+    }
+
+    fn parse_bit_and(&mut self) -> Result<Box<parser::ast::Expr>, Diag> {
+        let mut left = self.parse_equality()?;
+        while self.match_and(parser::token::TokenType::BitAnd, |_| true) {
+            let right = self.parse_equality()?;
+            left = Box::new(parser::ast::Expr::BinaryOp {
+                op: parser::ast::BinaryOperator::BitAnd,
+                lhs: left,
+                rhs: right,
+            });
+        }
+        Ok(left)
+    }
+
     fn parse_equality(&mut self) -> Result<Box<parser::ast::Expr>, Diag> {
         let mut left = self.parse_relational()?;
         while let Some(tok) = self.peek() {
@@ -273,7 +599,7 @@ impl Parser {
     }
 
     fn parse_relational(&mut self) -> Result<Box<parser::ast::Expr>, Diag> {
-        let mut left = self.parse_additive()?;
+        let mut left = self.parse_shift()?;
         while let Some(tok) = self.peek() {
             let op = if tok.matches(&parser::token::TokenType::Lt) {
                 parser::ast::BinaryOperator::Lt
@@ -287,6 +613,27 @@ impl Parser {
                 break;
             };
             self.eat();
+            let right = self.parse_shift()?;
+            left = Box::new(parser::ast::Expr::BinaryOp {
+                op,
+                lhs: left,
+                rhs: right,
+            });
+        }
+        Ok(left)
+    }
+
+    fn parse_shift(&mut self) -> Result<Box<parser::ast::Expr>, Diag> {
+        let mut left = self.parse_additive()?;
+        while let Some(tok) = self.peek() {
+            let op = if tok.matches(&parser::token::TokenType::Shl) {
+                parser::ast::BinaryOperator::Shl
+            } else if tok.matches(&parser::token::TokenType::Shr) {
+                parser::ast::BinaryOperator::Shr
+            } else {
+                break;
+            };
+            self.eat();
             let right = self.parse_additive()?;
             left = Box::new(parser::ast::Expr::BinaryOp {
                 op,
@@ -344,78 +691,408 @@ impl Parser {
     fn parse_unary(&mut self) -> Result<Box<parser::ast::Expr>, Diag> {
         if let Some(tok) = self.peek() {
             if tok.matches(&parser::token::TokenType::Sub) {
+                let start = self.peek_span();
                 self.eat();
+                // `-128i8` is in range even though `128i8` is not, so negative literals are folded here
+                if self.peek().is_some_and(|tok| tok.is_an_integer_literal()) {
+                    return self.parse_integer_literal(true, start);
+                }
                 let expr = self.parse_unary()?;
                 return Ok(Box::new(parser::ast::Expr::UnaryOp {
                     op: parser::ast::UnaryOperator::Neg,
                     expr,
+                    span: self.span_from(&start),
                 }));
             } else if tok.matches(&parser::token::TokenType::Not) {
+                let start = self.peek_span();
                 self.eat();
                 let expr = self.parse_unary()?;
                 return Ok(Box::new(parser::ast::Expr::UnaryOp {
                     op: parser::ast::UnaryOperator::Not,
                     expr,
+                    span: self.span_from(&start),
+                }));
+            } else if tok.matches(&parser::token::TokenType::BitNot) {
+                let start = self.peek_span();
+                self.eat();
+                let expr = self.parse_unary()?;
+                return Ok(Box::new(parser::ast::Expr::UnaryOp {
+                    op: parser::ast::UnaryOperator::BitNot,
+                    expr,
+                    span: self.span_from(&start),
                 }));
             } else if tok.matches(&parser::token::TokenType::Mul) {
+                let start = self.peek_span();
                 self.eat();
                 let expr = self.parse_unary()?;
                 return Ok(Box::new(parser::ast::Expr::UnaryOp {
                     op: parser::ast::UnaryOperator::Deref,
                     expr,
+                    span: self.span_from(&start),
                 }));
-            } else if tok.matches(&parser::token::TokenType::And) {
+            } else if tok.matches(&parser::token::TokenType::BitAnd) {
+                let start = self.peek_span();
                 self.eat();
+                let operand = self.peek_span();
                 let expr = self.parse_unary()?;
+                if !expr.is_lvalue() {
+                    return Err(Diag::NotAnLvalue("&".to_string(), self.span_from(&operand)));
+                }
                 return Ok(Box::new(parser::ast::Expr::UnaryOp {
                     op: parser::ast::UnaryOperator::AddrOf,
                     expr,
+                    span: self.span_from(&start),
                 }));
             }
         }
-        self.parse_primary()
+        self.parse_postfix()
+    }
+
+    fn parse_postfix(&mut self) -> Result<Box<parser::ast::Expr>, Diag> {
+        let mut expr = self.parse_primary()?;
+        while self.match_and(parser::token::TokenType::LParen, |_| true) {
+            let args = self.in_context(ParseContext::Expression, |p| {
+                let terminators = [parser::token::TokenType::SemiColon, parser::token::TokenType::RBrace];
+                p.parse_list(&terminators, |p| p.parse_expression().map(|arg| *arg))
+            });
+            expr = Box::new(parser::ast::Expr::Call { func: expr, args });
+        }
+        Ok(expr)
     }
 
     fn parse_primary(&mut self) -> Result<Box<parser::ast::Expr>, Diag> {
         // smallest unit of expressions
         if let Some(tok) = self.peek()  {
             if tok.is_an_integer_literal() {
-                if let parser::token::TokenType::IntegerLiteral(val) = tok.get_type() {
-                    let value: i64 = *val;  // goddamn rust borrow checker
-                    self.eat();
-                    return Ok(Box::new(parser::ast::Expr::IntegerLiteral(value)));
-                } else {
-                    unreachable!();
-                }
+                let start = self.peek_span();
+                return self.parse_integer_literal(false, start);
             }
 
-            if tok.is_an_identifier() {
-                if let parser::token::TokenType::Identifier(name) = tok.get_type() {
-                    let value = name.clone();
-                    self.eat();
-                    return Ok(Box::new(parser::ast::Expr::Identifier(value)))
+            if tok.is_a_char_literal()
+                && let parser::token::TokenType::CharLiteral(byte) = tok.get_type() {
+                let value = *byte;
+                self.eat();
+                return Ok(Box::new(parser::ast::Expr::CharLiteral(value)));
+            }
+
+            if tok.is_a_string_literal()
+                && let parser::token::TokenType::StringLiteral(bytes) = tok.get_type() {
+                let value = bytes.clone();
+                self.eat();
+                return Ok(Box::new(parser::ast::Expr::StringLiteral(value)));
+            }
+
+            if tok.is_an_identifier()
+                && let parser::token::TokenType::Identifier(name) = tok.get_type() {
+                let name = name.clone();
+                let span = tok.get_span().clone();
+                self.eat();
+                if self.peek_is(&TokenType::ColonColon) {
+                    return self.parse_variant(name, &Some(span));
                 }
+                return Ok(Box::new(parser::ast::Expr::Identifier { name, span }))
+            }
+
+            if tok.matches(&parser::token::TokenType::LParen) {
+                self.eat();
+                let expr = self.parse_expression()?;
+                self.expect(parser::token::TokenType::RParen)?;
+                return Ok(expr);
             }
 
             if tok.matches(&parser::token::TokenType::LBrace) {
                 self.eat();
-                let mut exprs = vec![];
-                while !self.match_and(parser::token::TokenType::RBrace, |_| true) {
-                    exprs.push(Box::new(self.parse_declaration()?));
-                    if self.peek().is_none() {
-                       return Err(Diag::EarlyEOF(self.current_span().clone()));
+                return self.in_context(ParseContext::Block, |p| p.parse_block());
+            }
+
+            if tok.matches(&parser::token::TokenType::If) {
+                self.eat();
+                return self.parse_if();
+            }
+
+            if tok.matches(&parser::token::TokenType::While)
+                || tok.matches(&parser::token::TokenType::Do)
+                || tok.matches(&parser::token::TokenType::For) {
+                return self.parse_loop(None);
+            }
+
+            // `'name: while ..`
+            if let parser::token::TokenType::Label(name) = tok.get_type() {
+                let label = parser::ast::LoopLabel { name: name.clone(), span: tok.get_span().clone() };
+                self.eat();
+                self.expect(parser::token::TokenType::Colon)?;
+                return self.parse_loop(Some(label));
+            }
+
+            if tok.matches(&parser::token::TokenType::Break) || tok.matches(&parser::token::TokenType::Continue) {
+                let is_break = tok.matches(&parser::token::TokenType::Break);
+                let span = tok.get_span().clone();
+                self.eat();
+                let label = self.parse_jump_label();
+                if is_break {
+                    return Ok(Box::new(parser::ast::Expr::Break { label, span }));
+                }
+                return Ok(Box::new(parser::ast::Expr::Continue { label, span }));
+            }
+
+            if tok.matches(&parser::token::TokenType::Switch) {
+                self.eat();
+                return self.parse_switch();
+            }
+
+            if tok.matches(&parser::token::TokenType::Fallthrough) {
+                let span = tok.get_span().clone();
+                self.eat();
+                return Ok(Box::new(parser::ast::Expr::Fallthrough { span }));
+            }
+
+            if tok.matches(&parser::token::TokenType::Return) {
+                let keyword = tok.get_span().clone();
+                self.eat();
+                // the value is left out when the statement or block ends right away
+                if self.peek_is(&parser::token::TokenType::SemiColon) || self.peek_is(&parser::token::TokenType::RBrace) {
+                    return Ok(Box::new(parser::ast::Expr::Return { value: None, span: keyword }));
+                }
+                let start = self.peek_span();
+                let value = self.parse_expression()?;
+                let span = self.span_from(&start);
+                return Ok(Box::new(parser::ast::Expr::Return { value: Some(value), span }));
+            }
+
+
+
+            if tok.matches(&parser::token::TokenType::Eof) {
+                return Err(Diag::EarlyEOF(tok.get_span().clone()));
+            }
+            Err(Diag::UnexpectedToken(tok.display(), tok.get_span().clone()))
+        } else {
+            Err(Diag::EarlyEOF(self.current_span().clone()))
+        }
+    }
+
+    // the declarations of a block up to and including the `}`, the `{` is already eaten.
+    // a broken declaration is skipped so the rest of the block is still checked
+    fn parse_block(&mut self) -> Result<Box<parser::ast::Expr>, Diag> {
+        let mut exprs = vec![];
+        let mut value = None;
+        while !self.match_and(parser::token::TokenType::RBrace, |_| true) {
+            if self.peek_is(&parser::token::TokenType::Eof) {
+                return Err(Diag::EarlyEOF(self.current_span().clone()));
+            }
+            let start = self.position;
+            match self.parse_declaration() {
+                Ok(decl) => {
+                    // only an expression left without its ';' is the value of the block
+                    let has_value = matches!(&decl, parser::ast::DeclType::SideEffect(expr) if expr.has_value())
+                        && !self.token_buffer[self.position - 1].matches(&parser::token::TokenType::SemiColon);
+                    let start_span = Some(self.token_buffer[start].get_span().clone());
+                    value = has_value.then(|| self.span_from(&start_span));
+                    exprs.push(decl);
+                }
+                Err(e) => {
+                    self.recover(e);
+                    if self.position == start {
+                        self.eat();
                     }
                 }
-                self.expect(parser::token::TokenType::RBrace);
-                return Ok(Box::new(parser::ast::Expr::CompoundExpr { expressions: exprs }));
             }
+        }
+        let end = self.token_buffer[self.position - 1].get_span().clone();
+        Ok(Box::new(parser::ast::Expr::CompoundExpr { expressions: exprs, value, end }))
+    }
+
+    // `if cond { .. }` with an optional `else { .. }` or `else if ..`, the `if` is already eaten
+    fn parse_if(&mut self) -> Result<Box<parser::ast::Expr>, Diag> {
+        let cond = self.parse_expression()?;
+        let then_branch = self.parse_braced_block()?;
+        let else_branch = if self.match_and(parser::token::TokenType::Else, |_| true) {
+            if self.match_and(parser::token::TokenType::If, |_| true) {
+                Some(self.parse_if()?)
+            } else {
+                Some(self.parse_braced_block()?)
+            }
+        } else {
+            None
+        };
+        Ok(Box::new(parser::ast::Expr::If { cond, then_branch, else_branch }))
+    }
 
+    // a `while`, `do`-`while` or `for` loop, `label` is the `'name:` written in front of it
+    fn parse_loop(&mut self, label: Option<parser::ast::LoopLabel>) -> Result<Box<parser::ast::Expr>, Diag> {
+        let Some(tok) = self.peek() else {
+            return Err(Diag::EarlyEOF(self.current_span().clone()));
+        };
+        match tok.get_type() {
+            parser::token::TokenType::While => {
+                self.eat();
+                let cond = self.parse_expression()?;
+                let body = self.parse_braced_block()?;
+                Ok(Box::new(parser::ast::Expr::While { label, cond, body }))
+            }
+            parser::token::TokenType::Do => {
+                self.eat();
+                let body = self.parse_braced_block()?;
+                self.expect(parser::token::TokenType::While)?;
+                let cond = self.parse_expression()?;
+                Ok(Box::new(parser::ast::Expr::DoWhile { label, body, cond }))
+            }
+            parser::token::TokenType::For => {
+                self.eat();
+                self.parse_for(label)
+            }
+            parser::token::TokenType::Eof => {
+                Err(Diag::EarlyEOF(tok.get_span().clone()))
+            }
+            // only loops can be labeled
+            _ => {
+                Err(Diag::UnexpectedToken(tok.display(), tok.get_span().clone()))
+            }
+        }
+    }
 
+    // the optional `'name` right after `break` or `continue`
+    fn parse_jump_label(&mut self) -> Option<parser::ast::LoopLabel> {
+        let tok = self.peek()?;
+        let parser::token::TokenType::Label(name) = tok.get_type() else {
+            return None;
+        };
+        let label = parser::ast::LoopLabel { name: name.clone(), span: tok.get_span().clone() };
+        self.eat();
+        Some(label)
+    }
 
-            todo!("{:?}", tok.display());
+    // `for init; cond; step { .. }` where every clause may be left out, the `for` is already eaten
+    fn parse_for(&mut self, label: Option<parser::ast::LoopLabel>) -> Result<Box<parser::ast::Expr>, Diag> {
+        let init = if self.match_and(parser::token::TokenType::SemiColon, |_| true) {
+            None
+        } else if self.peek_is(&parser::token::TokenType::Var) {
+            // eats the ';' itself
+            Some(Box::new(self.parse_variable_decl()?))
+        } else {
+            let expr = self.parse_expression()?;
+            self.expect(parser::token::TokenType::SemiColon)?;
+            Some(Box::new(parser::ast::DeclType::SideEffect(*expr)))
+        };
+        let cond = if self.peek_is(&parser::token::TokenType::SemiColon) {
+            None
+        } else {
+            Some(self.parse_expression()?)
+        };
+        self.expect(parser::token::TokenType::SemiColon)?;
+        let step = if self.peek_is(&parser::token::TokenType::LBrace) {
+            None
         } else {
-            return Err(Diag::EarlyEOF(self.current_span().clone())); 
+            Some(self.parse_expression()?)
+        };
+        let body = self.parse_braced_block()?;
+        Ok(Box::new(parser::ast::Expr::For { label, init, cond, step, body }))
+    }
+
+    // `switch value { case 1, 2 { .. } default { .. } }`, the `switch` is already eaten
+    fn parse_switch(&mut self) -> Result<Box<parser::ast::Expr>, Diag> {
+        let start = self.peek_span();
+        let scrutinee = self.parse_expression()?;
+        let span = self.span_from(&start);
+        self.expect(parser::token::TokenType::LBrace)?;
+        let mut cases = vec![];
+        while !self.match_and(parser::token::TokenType::RBrace, |_| true) {
+            let Some(tok) = self.peek() else {
+                return Err(Diag::EarlyEOF(self.current_span().clone()));
+            };
+            let keyword = tok.get_span().clone();
+            let values = match tok.get_type() {
+                parser::token::TokenType::Case => {
+                    self.eat();
+                    let mut values = vec![self.parse_case_value()?];
+                    while self.match_and(parser::token::TokenType::Comma, |_| true) {
+                        values.push(self.parse_case_value()?);
+                    }
+                    values
+                }
+                parser::token::TokenType::Default => {
+                    self.eat();
+                    vec![]
+                }
+                parser::token::TokenType::Eof => return Err(Diag::EarlyEOF(keyword)),
+                _ => return Err(Diag::UnexpectedToken(tok.display(), keyword)),
+            };
+            let body = self.parse_braced_block()?;
+            cases.push(parser::ast::SwitchCase { values, span: keyword, body });
         }
+        Ok(Box::new(parser::ast::Expr::Switch { scrutinee, span, cases }))
+    }
+
+    // an integer or character literal or an enum variant after `case`, integers may be negated
+    fn parse_case_value(&mut self) -> Result<parser::ast::CaseValue, Diag> {
+        let start = self.peek_span();
+        let negated = self.match_and(parser::token::TokenType::Sub, |_| true);
+        let Some(tok) = self.peek() else {
+            return Err(Diag::EarlyEOF(self.current_span().clone()));
+        };
+        match tok.get_type() {
+            parser::token::TokenType::IntegerLiteral(..) => {
+                let literal = self.parse_integer_literal(negated, start.clone())?;
+                let parser::ast::Expr::IntegerLiteral { value, ty } = *literal else {
+                    unreachable!();
+                };
+                Ok(parser::ast::CaseValue { value, ty: *ty, span: self.span_from(&start) })
+            }
+            parser::token::TokenType::CharLiteral(byte) if !negated => {
+                let value = *byte as i64;
+                self.eat();
+                Ok(parser::ast::CaseValue { value, ty: parser::ast::Type::Char, span: self.span_from(&start) })
+            }
+            parser::token::TokenType::Identifier(name) if !negated => {
+                let name = name.clone();
+                self.eat();
+                let variant = self.parse_variant(name, &start)?;
+                let parser::ast::Expr::IntegerLiteral { value, ty } = *variant else {
+                    unreachable!();
+                };
+                Ok(parser::ast::CaseValue { value, ty: *ty, span: self.span_from(&start) })
+            }
+            parser::token::TokenType::Eof => {
+                Err(Diag::EarlyEOF(tok.get_span().clone()))
+            }
+            _ => {
+                Err(Diag::UnexpectedToken(tok.display(), tok.get_span().clone()))
+            }
+        }
+    }
+
+    // a block that the grammar requires at this point, like the arms of an `if`
+    fn parse_braced_block(&mut self) -> Result<Box<parser::ast::Expr>, Diag> {
+        self.expect(parser::token::TokenType::LBrace)?;
+        self.in_context(ParseContext::Block, |p| p.parse_block())
+    }
+
+    // parses the integer literal at the current token, negated if a '-' starting at `start` came before it
+    fn parse_integer_literal(&mut self, negated: bool, start: Option<parser::token::Span>) -> Result<Box<parser::ast::Expr>, Diag> {
+        let Some(tok) = self.peek() else {
+            return Err(Diag::EarlyEOF(self.current_span().clone()));
+        };
+        let parser::token::TokenType::IntegerLiteral(magnitude, suffix) = tok.get_type() else {
+            unreachable!();
+        };
+        let (magnitude, suffix) = (*magnitude, *suffix);
+        self.eat();
+
+        let ty = match suffix {
+            Some(suffix) => parser::ast::Type::new_integer(suffix.bits, suffix.signed),
+            None => parser::ast::Type::new_int(),
+        };
+        let value = if negated { -(magnitude as i128) } else { magnitude as i128 };
+        let (min, max) = ty.integer_range().unwrap();
+        if value < min || value > max {
+            let suffix = suffix.map(|s| s.name()).unwrap_or_default();
+            return Err(Diag::IntegerOutOfRange {
+                err_loc: self.span_from(&start),
+                literal: format!("{}{}{}", if negated { "-" } else { "" }, magnitude, suffix),
+                ty: *ty,
+            });
+        }
+
+        Ok(Box::new(parser::ast::Expr::IntegerLiteral { value: value as i64, ty }))
     }
 
     fn parse_type(&mut self) -> Result<Box<parser::ast::Type>, Diag> {
@@ -423,7 +1100,15 @@ impl Parser {
             return Err(Diag::EarlyEOF(self.current_span().clone()));
         };
 
-        if tok.matches(&parser::token::TokenType::Int) {
+        if tok.matches(&parser::token::TokenType::Mul) {
+            // *T
+            self.eat();
+            return Ok(parser::ast::Type::new_pointer(self.parse_type()?));
+        } else if tok.matches(&parser::token::TokenType::BitAnd) {
+            // &T, read through but never written through
+            self.eat();
+            return Ok(parser::ast::Type::new_ref(self.parse_type()?));
+        } else if tok.matches(&parser::token::TokenType::Int) {
             self.eat();
             return Ok(parser::ast::Type::new_int());
         } else if tok.matches(&parser::token::TokenType::Char) {
@@ -432,6 +1117,19 @@ impl Parser {
         } else if tok.matches(&parser::token::TokenType::Void) {
             self.eat();
             return Ok(parser::ast::Type::new_void());
+        } else if let Some((bits, signed)) = match tok.get_type() {
+            TokenType::I8 => Some((8, true)),
+            TokenType::I16 => Some((16, true)),
+            TokenType::I32 => Some((32, true)),
+            TokenType::I64 => Some((64, true)),
+            TokenType::U8 => Some((8, false)),
+            TokenType::U16 => Some((16, false)),
+            TokenType::U32 => Some((32, false)),
+            TokenType::U64 => Some((64, false)),
+            _ => None,
+        } {
+            self.eat();
+            return Ok(parser::ast::Type::new_integer(bits, signed));
         } else if tok.is_an_identifier() {
             let name = if let parser::token::TokenType::Identifier(n) = tok.get_type() {
                 n
@@ -448,6 +1146,6 @@ impl Parser {
             }
             return Ok(type_name);
         }
-        todo!("{:?}", tok.display()); 
+        Err(Diag::UnexpectedToken(tok.display(), tok.get_span().clone()))
     }
 }
@@ -0,0 +1,84 @@
+use super::token::{FileId, Span};
+
+pub struct SourceFile {
+    name: String,
+    text: String,
+    // byte offset of the start of every line
+    line_starts: Vec<usize>,
+}
+
+// a line and column, both counted from 1, columns in characters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+// owns every loaded file, spans are resolved against it
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceFile {
+    fn new(name: String, text: String) -> Self {
+        let mut line_starts = vec![0];
+        line_starts.extend(text.match_indices('\n').map(|(i, _)| i + 1));
+        SourceFile { name, text, line_starts }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    // the location of the byte at `offset`, offsets past the end land on the end
+    pub fn location(&self, offset: usize) -> Location {
+        let offset = offset.min(self.text.len());
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let column = self.text[self.line_starts[line]..offset].chars().count() + 1;
+        Location { line: line + 1, column }
+    }
+
+    // the text of the 1-based `line`, without its line break
+    pub fn line(&self, line: usize) -> &str {
+        let start = self.line_starts[line - 1];
+        let end = self.line_starts.get(line).copied().unwrap_or(self.text.len());
+        self.text[start..end].trim_end_matches(['\n', '\r'])
+    }
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        SourceMap { files: Vec::new() }
+    }
+
+    pub fn add(&mut self, name: String, text: String) -> FileId {
+        self.files.push(SourceFile::new(name, text));
+        self.files.len() - 1
+    }
+
+    pub fn file(&self, id: FileId) -> &SourceFile {
+        &self.files[id]
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = (FileId, &SourceFile)> {
+        self.files.iter().enumerate()
+    }
+
+    pub fn path(&self, span: &Span) -> &str {
+        self.file(span.file()).name()
+    }
+
+    // where `span` starts
+    pub fn location(&self, span: &Span) -> Location {
+        self.file(span.file()).location(span.start())
+    }
+
+    // where `span` ends, the location just past its last character
+    pub fn end_location(&self, span: &Span) -> Location {
+        self.file(span.file()).location(span.end())
+    }
+}
@@ -1,5 +1,3 @@
-
-
 // Define the TokenType enum to represent different types of tokens
 #[derive(Debug, PartialEq, Clone)]
 pub enum TokenType {
@@ -8,11 +6,17 @@ pub enum TokenType {
     Int, Char, Struct, Enum,
     Return, If, Else, While, For, Break,
     Continue, Void, Const, Static, Extern, Typedef, Sizeof,
-    Switch, Case, Default, Do, Goto, Union, 
+    Switch, Case, Default, Fallthrough, Do, Goto, Union, 
+    I8, I16, I32, I64, U8, U16, U32, U64,
 
     // values
     Identifier(String),
-    IntegerLiteral(i64),
+    IntegerLiteral(u64, Option<IntSuffix>),
+    // the bytes after escapes are resolved, without a terminator
+    StringLiteral(Vec<u8>),
+    CharLiteral(u8),
+    // a loop label, the name without its leading '\''
+    Label(String),
 
     // operators
     Add,
@@ -24,41 +28,163 @@ pub enum TokenType {
     Assign,
     And, Or, Not,
     Inc, Dec,
-    AddrOf, Deref,
     Mod, Xor,
     Shl, Shr,
+    BitAnd, BitOr, BitNot,
+    AddAssign, SubAssign, MulAssign, DivAssign, ModAssign,
+    AndAssign, OrAssign, XorAssign, ShlAssign, ShrAssign,
 
     // punctuations
     LParen, RParen,
     LBrace, RBrace,
-    SemiColon, Colon, 
+    SemiColon, Colon, ColonColon,
     Comma, 
     LArrow, RArrow,
 
     Eof,
 }
 
+impl TokenType {
+    // how the token is written in the source, or a description for tokens with a value
+    pub fn display(&self) -> String {
+        match self {
+            TokenType::Fn  => "fn".to_string(),
+            TokenType::Var => "var".to_string(),
+            TokenType::Int => "int".to_string(),
+            TokenType::Char => "char".to_string(),
+            TokenType::Struct => "struct".to_string(),
+            TokenType::Enum => "enum".to_string(),
+            TokenType::Return => "return".to_string(),
+            TokenType::If => "if".to_string(),
+            TokenType::Else => "else".to_string(),
+            TokenType::While => "while".to_string(),
+            TokenType::For => "for".to_string(),
+            TokenType::Break => "break".to_string(),
+            TokenType::Continue => "continue".to_string(),
+            TokenType::Void => "void".to_string(),
+            TokenType::Const => "const".to_string(),
+            TokenType::Static => "static".to_string(),
+            TokenType::Extern => "extern".to_string(),
+            TokenType::Typedef => "typedef".to_string(),
+            TokenType::Sizeof => "sizeof".to_string(),
+            TokenType::Switch => "switch".to_string(),
+            TokenType::Case => "case".to_string(),
+            TokenType::Default => "default".to_string(),
+            TokenType::Fallthrough => "fallthrough".to_string(),
+            TokenType::Do => "do".to_string(),
+            TokenType::Goto => "goto".to_string(),
+            TokenType::Union => "union".to_string(),
+            TokenType::I8 => "i8".to_string(),
+            TokenType::I16 => "i16".to_string(),
+            TokenType::I32 => "i32".to_string(),
+            TokenType::I64 => "i64".to_string(),
+            TokenType::U8 => "u8".to_string(),
+            TokenType::U16 => "u16".to_string(),
+            TokenType::U32 => "u32".to_string(),
+            TokenType::U64 => "u64".to_string(),
+            TokenType::Identifier(name) => format!("identifier({})", name),
+            TokenType::IntegerLiteral(value, None) => format!("integer({})", value),
+            TokenType::IntegerLiteral(value, Some(suffix)) => format!("integer({}{})", value, suffix.name()),
+            TokenType::StringLiteral(bytes) => format!("string(\"{}\")", escape_bytes(bytes)),
+            TokenType::CharLiteral(b'\'') => "char('\\'')".to_string(),
+            TokenType::CharLiteral(byte) => format!("char('{}')", escape_bytes(&[*byte])),
+            TokenType::Label(name) => format!("label('{})", name),
+            TokenType::Add => "+".to_string(),
+            TokenType::Sub => "-".to_string(),
+            TokenType::Mul => "*".to_string(),
+            TokenType::Div => "/".to_string(),
+            TokenType::Eq => "==".to_string(),
+            TokenType::Neq => "!=".to_string(),
+            TokenType::Lt => "<".to_string(),
+            TokenType::Gt => ">".to_string(),
+            TokenType::Leq => "<=".to_string(),
+            TokenType::Geq => ">=".to_string(),
+            TokenType::Assign => "=".to_string(),
+            TokenType::And => "&&".to_string(),
+            TokenType::Or => "||".to_string(),
+            TokenType::Not => "!".to_string(),
+            TokenType::Inc => "++".to_string(),
+            TokenType::Dec => "--".to_string(),
+            TokenType::Mod => "%".to_string(),
+            TokenType::Xor => "^".to_string(),
+            TokenType::Shl => "<<".to_string(),
+            TokenType::Shr => ">>".to_string(),
+            TokenType::BitAnd => "&".to_string(),
+            TokenType::BitOr => "|".to_string(),
+            TokenType::BitNot => "~".to_string(),
+            TokenType::AddAssign => "+=".to_string(),
+            TokenType::SubAssign => "-=".to_string(),
+            TokenType::MulAssign => "*=".to_string(),
+            TokenType::DivAssign => "/=".to_string(),
+            TokenType::ModAssign => "%=".to_string(),
+            TokenType::AndAssign => "&=".to_string(),
+            TokenType::OrAssign => "|=".to_string(),
+            TokenType::XorAssign => "^=".to_string(),
+            TokenType::ShlAssign => "<<=".to_string(),
+            TokenType::ShrAssign => ">>=".to_string(),
+            TokenType::LParen => "(".to_string(),
+            TokenType::RParen => ")".to_string(),
+            TokenType::LBrace => "{".to_string(),
+            TokenType::RBrace => "}".to_string(),
+            TokenType::SemiColon => ";".to_string(),
+            TokenType::Colon => ":".to_string(),
+            TokenType::ColonColon => "::".to_string(),
+            TokenType::Comma => ",".to_string(),
+            TokenType::LArrow => "<-".to_string(),
+            TokenType::RArrow => "->".to_string(),
+            TokenType::Eof => "EOF".to_string(),
+        }
+    }
+}
+
+// the exact-width type written after an integer literal, as in `42u8`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IntSuffix {
+    pub bits: u8,
+    pub signed: bool,
+}
+
+impl IntSuffix {
+    pub fn name(&self) -> String {
+        format!("{}{}", if self.signed { "i" } else { "u" }, self.bits)
+    }
+}
+
+// identifies a file loaded into the `SourceMap`
+pub type FileId = usize;
+
+// a half-open byte range into one source file
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Span {
-   start_line: usize,
-   end_line:   usize,
-   start_col:  usize,
-   end_col:    usize,
+   file:  FileId,
+   start: usize,
+   end:   usize,
 }
 
 impl Span {
-    pub fn new(start_line: usize, end_line: usize, start_col: usize, end_col: usize) -> Self {
-        Span { start_line, end_line, start_col, end_col }
+    pub fn new(file: FileId, start: usize, end: usize) -> Self {
+        Span { file, start, end }
     }
 
     pub fn merge(&self, other: &Span) -> Self {
         Span {
-            start_line: self.start_line,
-            end_line: other.end_line,
-            start_col: self.start_col,
-            end_col: other.end_col,
+            file: self.file,
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
         }
     }
+
+    pub fn file(&self) -> FileId {
+        self.file
+    }
+
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn end(&self) -> usize {
+        self.end
+    }
 }
 
 pub struct Token {
@@ -72,39 +198,7 @@ impl Token {
     }
 
     pub fn display(&self) -> String {
-        match &self.token_type {
-            TokenType::Fn  => "fn".to_string(),
-            TokenType::Var => "var".to_string(),
-            TokenType::Const => "const".to_string(),
-            TokenType::Int => "int".to_string(),
-            TokenType::Char => "char".to_string(),
-            TokenType::Struct => "struct".to_string(),
-            TokenType::Enum => "enum".to_string(),
-            TokenType::Identifier(name) => format!("identifier({})", name),
-            TokenType::IntegerLiteral(value) => format!("integer({})", value),
-            TokenType::Add => "+".to_string(),
-            TokenType::Sub => "-".to_string(),
-            TokenType::Mul => "*".to_string(),
-            TokenType::Div => "/".to_string(),
-            TokenType::LParen => "(".to_string(),
-            TokenType::RParen => ")".to_string(),
-            TokenType::LBrace => "{".to_string(),
-            TokenType::RBrace => "}".to_string(),
-            TokenType::SemiColon => ";".to_string(),
-            TokenType::Colon => ":".to_string(),
-            TokenType::Comma => ",".to_string(),
-            TokenType::Eq => "=".to_string(),
-            TokenType::Return => "return".to_string(),
-            TokenType::If => "if".to_string(),
-            TokenType::Else => "else".to_string(),
-            TokenType::While => "while".to_string(),
-            TokenType::For => "for".to_string(),
-            TokenType::Break => "break".to_string(),
-            TokenType::LArrow => "->".to_string(),
-            TokenType::RArrow => "<-".to_string(),
-            TokenType::Eof => "EOF".to_string(),
-            _ => todo!(),
-        }
+        self.token_type.display()
     }
 
     pub fn get_span(&self) -> &Span {
@@ -112,33 +206,44 @@ impl Token {
     }
 
     pub fn matches(&self, other: &TokenType) -> bool {
-        return self.token_type == *other;
-    }
-
-    pub fn is_a_name(&self) -> bool {
-        return match self.token_type {
-            TokenType::Identifier(_) => true,
-            // other possible type names will be covered later
-            TokenType::Int | TokenType::Char | TokenType::Void | TokenType::Struct | TokenType::Enum => true,
-            _ => false,
-        }
+        self.token_type == *other
     }
 
     pub fn is_an_identifier(&self) -> bool {
-        return match self.token_type {
-            TokenType::Identifier(_) => true,
-            _ => false,
-        }
+        matches!(self.token_type, TokenType::Identifier(_))
     }
 
     pub fn is_an_integer_literal(&self) -> bool {
-        return match self.token_type {
-            TokenType::IntegerLiteral(_) => true,
-            _ => false,
-        }
+        matches!(self.token_type, TokenType::IntegerLiteral(..))
+    }
+
+    pub fn is_a_string_literal(&self) -> bool {
+        matches!(self.token_type, TokenType::StringLiteral(_))
+    }
+
+    pub fn is_a_char_literal(&self) -> bool {
+        matches!(self.token_type, TokenType::CharLiteral(_))
     }
 
     pub fn get_type(&self) -> &TokenType {
         &self.token_type
     }
 }
+
+// renders bytes the way they would be written inside a Cx string literal
+pub fn escape_bytes(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for &byte in bytes {
+        match byte {
+            b'\n' => out.push_str("\\n"),
+            b'\t' => out.push_str("\\t"),
+            b'\r' => out.push_str("\\r"),
+            b'\0' => out.push_str("\\0"),
+            b'\\' => out.push_str("\\\\"),
+            b'"' => out.push_str("\\\""),
+            0x20..=0x7e => out.push(byte as char),
+            _ => out.push_str(&format!("\\x{:02x}", byte)),
+        }
+    }
+    out
+}
@@ -0,0 +1,517 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::diag::diag::{Diag, Diagnostic, Lint};
+use crate::parser::ast;
+use crate::parser::token::Span;
+use crate::sema::types;
+
+// Checks the parsed program for mistakes the parser cannot see.
+// nothing here stops code generation, problems are returned as diagnostics
+pub struct Checker {
+    // the locals of every open block, innermost last
+    scopes: Vec<Vec<Local>>,
+    // the labels of the loops enclosing the current expression, innermost last
+    loops: Vec<Option<ast::LoopLabel>>,
+    // for every switch arm around the current expression, whether another arm follows it
+    switch_arms: Vec<bool>,
+    // the name and return type of the function being checked
+    function: Option<(String, ast::Type)>,
+    // whether the expression being checked is the value its function returns
+    tail: bool,
+    // the parameters of that function, they are never reported as unused
+    params: HashMap<String, ast::Type>,
+    // the types of globals and the return types of functions, declared anywhere in the program
+    globals: HashMap<String, ast::Type>,
+    functions: HashMap<String, ast::Type>,
+    // the parameter types of every function
+    signatures: HashMap<String, Vec<ast::Type>>,
+    // the names of the enums, values of their type are integers that can be switched on
+    enums: HashSet<String>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+struct Local {
+    name: String,
+    span: Span,
+    var_type: ast::Type,
+    used: bool,
+}
+
+pub fn check(program: &[ast::DeclType]) -> Vec<Diagnostic> {
+    let mut checker = Checker {
+        scopes: Vec::new(),
+        loops: Vec::new(),
+        switch_arms: Vec::new(),
+        function: None,
+        tail: false,
+        params: HashMap::new(),
+        globals: HashMap::new(),
+        functions: HashMap::new(),
+        signatures: HashMap::new(),
+        enums: HashSet::new(),
+        diagnostics: Vec::new(),
+    };
+    // functions and globals may be used before they are declared
+    for decl in program.iter() {
+        match decl {
+            ast::DeclType::FunctionDecl { name, func_type, params, .. } => {
+                checker.functions.insert(name.clone(), (**func_type).clone());
+                checker.signatures.insert(name.clone(), params.iter().map(|param| param.ptype().clone()).collect());
+            }
+            ast::DeclType::VariableDecl { name, var_type, .. } => {
+                checker.globals.insert(name.clone(), (**var_type).clone());
+            }
+            ast::DeclType::EnumDecl { name, .. } => {
+                checker.enums.insert(name.clone());
+            }
+            ast::DeclType::SideEffect(_) => {}
+        }
+    }
+    for decl in program.iter() {
+        checker.check_global(decl);
+    }
+    // scopes are closed inside out, report in source order instead
+    checker.diagnostics.sort_by_key(|d| (d.span.file(), d.span.start()));
+    checker.diagnostics
+}
+
+impl Checker {
+    fn push_scope(&mut self) {
+        self.scopes.push(Vec::new());
+    }
+
+    fn pop_scope(&mut self) {
+        for local in self.scopes.pop().unwrap() {
+            // a leading '_' marks a variable as deliberately unused
+            if local.used || local.name.starts_with('_') {
+                continue;
+            }
+            self.diagnostics.push(
+                Diagnostic::warning(Lint::UnusedVariables, format!("unused variable `{}`", local.name), local.span)
+                    .with_help(format!("if this is intentional, prefix it with an underscore: `_{}`", local.name)),
+            );
+        }
+    }
+
+    // inner blocks may shadow a name, one block may only declare it once
+    fn declare(&mut self, name: &str, span: &Span, var_type: &ast::Type) {
+        if let Some(first) = self.scopes.last_mut().unwrap().iter_mut().find(|l| l.name == name) {
+            // the error covers it, it is not reported as unused as well
+            first.used = true;
+            self.diagnostics.push(Diagnostic::from(Diag::RedefinedVariable {
+                err_loc: span.clone(),
+                var_name: name.to_string(),
+                prev_decl: first.span.clone(),
+            }));
+        }
+        self.scopes.last_mut().unwrap().push(Local {
+            name: name.to_string(),
+            span: span.clone(),
+            var_type: var_type.clone(),
+            used: false,
+        });
+    }
+
+    // marks the innermost local called `name` as used, globals are not tracked
+    fn use_name(&mut self, name: &str) {
+        let local = self.scopes.iter_mut().rev().find_map(|scope| scope.iter_mut().rev().find(|l| l.name == name));
+        if let Some(local) = local {
+            local.used = true;
+        }
+    }
+
+    fn check_global(&mut self, decl: &ast::DeclType) {
+        match decl {
+            ast::DeclType::FunctionDecl { name, func_type, params, body: Some(body) } => {
+                let params = params.iter()
+                    .filter_map(|param| Some((param.name()?.to_string(), param.ptype().clone())))
+                    .collect();
+                self.function = Some((name.clone(), (**func_type).clone()));
+                self.params = params;
+                self.tail = true;
+                self.check_expr(body);
+                if **func_type != ast::Type::Void && !body.has_value() && !body.diverges()
+                    && let ast::Expr::CompoundExpr { end, .. } = body.as_ref() {
+                    self.diagnostics.push(
+                        Diagnostic::from(Diag::MissingReturnValue { err_loc: end.clone(), function: name.clone() })
+                            .with_note(format!("`{}` is declared to return `{}`", name, func_type)),
+                    );
+                }
+                self.function = None;
+            }
+            ast::DeclType::FunctionDecl { body: None, .. } => {}
+            // globals may be used from other files
+            ast::DeclType::VariableDecl { name, init, span, .. } => {
+                if let Some(init) = init {
+                    self.check_expr(init);
+                    // the value is laid out by the assembler, nothing runs before main
+                    let constant = init.const_value().is_some() || matches!(**init, ast::Expr::StringLiteral(_));
+                    if !constant {
+                        self.diagnostics.push(Diagnostic::from(Diag::NonConstantGlobal {
+                            err_loc: span.clone(),
+                            var_name: name.clone(),
+                        }));
+                    }
+                }
+            }
+            // the parser checked the variants
+            ast::DeclType::EnumDecl { .. } => {}
+            ast::DeclType::SideEffect(_) => unreachable!("the parser rejects expressions outside of functions"),
+        }
+    }
+
+    fn check_local(&mut self, decl: &ast::DeclType) {
+        match decl {
+            ast::DeclType::VariableDecl { name, var_type, init, span, .. } => {
+                if let Some(init) = init {
+                    self.check_expr(init);
+                    self.check_stored(var_type, init, init.span().unwrap_or(span));
+                }
+                self.declare(name, span, var_type);
+            }
+            ast::DeclType::SideEffect(expr) => self.check_expr(expr),
+            ast::DeclType::FunctionDecl { .. } => unreachable!("the parser rejects nested functions"),
+            ast::DeclType::EnumDecl { .. } => unreachable!("enums are only declared at the top level"),
+        }
+    }
+
+    fn check_loop_body(&mut self, label: &Option<ast::LoopLabel>, body: &ast::Expr) {
+        if let Some(label) = label {
+            let outer = self.loops.iter().flatten().find(|outer| outer.name == label.name);
+            if let Some(outer) = outer {
+                self.diagnostics.push(Diagnostic::from(Diag::ShadowedLabel {
+                    err_loc: label.span.clone(),
+                    label: label.name.clone(),
+                    prev_decl: outer.span.clone(),
+                }));
+            }
+        }
+        self.loops.push(label.clone());
+        self.check_expr(body);
+        self.loops.pop();
+    }
+
+    fn check_expr(&mut self, expr: &ast::Expr) {
+        // only the arms of blocks, `if`s and `switch`es pass it on
+        let tail = std::mem::replace(&mut self.tail, false);
+        match expr {
+            ast::Expr::IntegerLiteral { .. } | ast::Expr::StringLiteral(_) | ast::Expr::CharLiteral(_) => {}
+            ast::Expr::Identifier { name, span } => {
+                if self.lookup_type(name).is_none() && !self.functions.contains_key(name) {
+                    self.diagnostics.push(Diagnostic::from(Diag::UndefinedVariable {
+                        err_loc: span.clone(),
+                        var_name: name.clone(),
+                    }));
+                }
+                self.use_name(name);
+            }
+            ast::Expr::Variable(name) => self.use_name(name),
+            ast::Expr::BinaryOp { lhs, rhs, op } => {
+                self.check_expr(lhs);
+                self.check_expr(rhs);
+                if *op == ast::BinaryOperator::Assign {
+                    self.check_written(lhs);
+                    if let (Some(target), Some(span)) = (self.type_of(lhs), lhs.span()) {
+                        self.check_stored(&target, rhs, rhs.span().unwrap_or(span));
+                    }
+                }
+            }
+            ast::Expr::UnaryOp { op, expr, span } => {
+                self.check_expr(expr);
+                let operation = match op {
+                    ast::UnaryOperator::Neg => "-",
+                    ast::UnaryOperator::BitNot => "~",
+                    ast::UnaryOperator::Deref => "*",
+                    ast::UnaryOperator::Not | ast::UnaryOperator::AddrOf => return,
+                };
+                let valid = |ty: &ast::Type| match op {
+                    ast::UnaryOperator::Deref => matches!(ty, ast::Type::PointerType { .. } | ast::Type::RefType { .. }),
+                    _ => ty.is_integer(),
+                };
+                if let Some(ty) = self.type_of(expr)
+                    && !valid(&ty) {
+                    self.diagnostics.push(Diagnostic::from(Diag::InvalidOperation {
+                        err_loc: span.clone(),
+                        operation: operation.to_string(),
+                        operand_type: ty,
+                    }));
+                }
+            }
+            ast::Expr::Call { func, args } => {
+                self.check_expr(func);
+                // a variable shadows the function of the same name, and no variable can hold a function yet
+                if let ast::Expr::Identifier { name, span } = func.as_ref()
+                    && let Some(ty) = self.lookup_type(name) {
+                    self.diagnostics.push(Diagnostic::from(Diag::InvalidOperation {
+                        err_loc: span.clone(),
+                        operation: "()".to_string(),
+                        operand_type: ty,
+                    }));
+                }
+                for arg in args.iter() {
+                    self.check_expr(arg);
+                }
+                if let ast::Expr::Identifier { name, span } = func.as_ref()
+                    && let Some(params) = self.signatures.get(name).cloned() {
+                    for (param, arg) in params.iter().zip(args.iter()) {
+                        self.check_stored(param, arg, arg.span().unwrap_or(span));
+                    }
+                }
+            }
+            ast::Expr::CompoundAssign { target, value, .. } => {
+                self.check_expr(target);
+                self.check_expr(value);
+                self.check_written(target);
+            }
+            ast::Expr::If { cond, then_branch, else_branch } => {
+                self.check_expr(cond);
+                self.tail = tail;
+                self.check_expr(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.tail = tail;
+                    self.check_expr(else_branch);
+                }
+            }
+            ast::Expr::While { label, cond, body } | ast::Expr::DoWhile { label, body, cond } => {
+                self.check_expr(cond);
+                self.check_loop_body(label, body);
+            }
+            ast::Expr::For { label, init, cond, step, body } => {
+                // a variable declared by `init` lives as long as the loop
+                self.push_scope();
+                if let Some(init) = init {
+                    self.check_local(init);
+                }
+                if let Some(cond) = cond {
+                    self.check_expr(cond);
+                }
+                if let Some(step) = step {
+                    self.check_expr(step);
+                }
+                self.check_loop_body(label, body);
+                self.pop_scope();
+            }
+            ast::Expr::Break { label, span } | ast::Expr::Continue { label, span } => {
+                if let Some(label) = label {
+                    let declared = self.loops.iter().flatten().any(|outer| outer.name == label.name);
+                    if !declared {
+                        self.diagnostics.push(Diagnostic::from(Diag::UndeclaredLabel {
+                            err_loc: label.span.clone(),
+                            label: label.name.clone(),
+                        }));
+                    }
+                } else if self.loops.is_empty() {
+                    let keyword = if matches!(expr, ast::Expr::Break { .. }) { "break" } else { "continue" };
+                    self.diagnostics.push(Diagnostic::from(Diag::OutsideOfLoop {
+                        err_loc: span.clone(),
+                        keyword: keyword.to_string(),
+                    }));
+                }
+            }
+            ast::Expr::Switch { scrutinee, span, cases } => {
+                self.check_expr(scrutinee);
+                if let Some(ty) = self.type_of(scrutinee) {
+                    if !ty.is_integer() && !self.is_enum(&ty) {
+                        self.diagnostics.push(Diagnostic::from(Diag::InvalidOperation {
+                            err_loc: span.clone(),
+                            operation: "switch".to_string(),
+                            operand_type: ty,
+                        }));
+                    } else {
+                        self.check_case_types(&ty, cases);
+                    }
+                }
+                self.check_cases(cases);
+                for (i, case) in cases.iter().enumerate() {
+                    self.switch_arms.push(i + 1 < cases.len());
+                    self.tail = tail;
+                    self.check_expr(&case.body);
+                    self.switch_arms.pop();
+                }
+            }
+            ast::Expr::Fallthrough { span } => {
+                if self.switch_arms.last() != Some(&true) {
+                    self.diagnostics.push(Diagnostic::from(Diag::InvalidFallthrough { err_loc: span.clone() }));
+                }
+            }
+            ast::Expr::Return { value, span } => {
+                if let Some(value) = value {
+                    self.check_expr(value);
+                }
+                self.check_return(value, span);
+            }
+            ast::Expr::CompoundExpr { expressions, value, .. } => {
+                self.push_scope();
+                for (i, decl) in expressions.iter().enumerate() {
+                    self.tail = tail && value.is_some() && i + 1 == expressions.len();
+                    self.check_local(decl);
+                }
+                // the value of the body is returned, arms are checked on their own.
+                // a void function drops it
+                if tail
+                    && self.function.as_ref().is_some_and(|(_, ty)| *ty != ast::Type::Void)
+                    && let Some(span) = value
+                    && let Some(ast::DeclType::SideEffect(last)) = expressions.last()
+                    && !matches!(last, ast::Expr::If { .. } | ast::Expr::Switch { .. } | ast::Expr::CompoundExpr { .. })
+                    && let Some(got) = self.type_of(last) {
+                    self.check_returned(got, span);
+                }
+                self.pop_scope();
+            }
+        }
+    }
+
+    // an enum is only matched against its own variants, integers against integers and chars
+    fn check_case_types(&mut self, scrutinee: &ast::Type, cases: &[ast::SwitchCase]) {
+        for value in cases.iter().flat_map(|case| case.values.iter()) {
+            let matches = if self.is_enum(scrutinee) || self.is_enum(&value.ty) {
+                *scrutinee == value.ty
+            } else {
+                value.ty.is_integer()
+            };
+            if !matches {
+                self.diagnostics.push(Diagnostic::from(Diag::TypeMismatch {
+                    err_loc: value.span.clone(),
+                    expected: Box::new(scrutinee.clone()),
+                    got: Box::new(value.ty.clone()),
+                }));
+            }
+        }
+    }
+
+    fn is_enum(&self, ty: &ast::Type) -> bool {
+        matches!(ty, ast::Type::TypeName(name) if self.enums.contains(name))
+    }
+
+    // every value may only be matched by one arm, and there is at most one `default`
+    fn check_cases(&mut self, cases: &[ast::SwitchCase]) {
+        let mut seen: HashMap<i64, &Span> = HashMap::new();
+        let mut default: Option<&Span> = None;
+        for case in cases.iter() {
+            if case.is_default() {
+                if let Some(first) = default {
+                    self.diagnostics.push(Diagnostic::from(Diag::DuplicateCase {
+                        err_loc: case.span.clone(),
+                        case: "default".to_string(),
+                        prev_decl: first.clone(),
+                    }));
+                } else {
+                    default = Some(&case.span);
+                }
+            }
+            for value in case.values.iter() {
+                if let Some(first) = seen.get(&value.value) {
+                    self.diagnostics.push(Diagnostic::from(Diag::DuplicateCase {
+                        err_loc: value.span.clone(),
+                        case: format!("case {}", value.value),
+                        prev_decl: (*first).clone(),
+                    }));
+                } else {
+                    seen.insert(value.value, &value.span);
+                }
+            }
+        }
+    }
+
+    // a `&T` is only read through, it is not written through or stored as a `*T`
+    fn check_stored(&mut self, expected: &ast::Type, value: &ast::Expr, span: &Span) {
+        if let Some(got) = self.type_of(value)
+            && let Some(diag) = reference_mismatch(expected, &got, span) {
+            self.diagnostics.push(diag);
+        }
+    }
+
+    fn check_written(&mut self, target: &ast::Expr) {
+        if let ast::Expr::UnaryOp { op: ast::UnaryOperator::Deref, expr, span } = target
+            && let Some(ast::Type::RefType { to, mutable: false }) = self.type_of(expr) {
+            self.diagnostics.push(
+                Diagnostic::from(Diag::ReferenceMutMismatch {
+                    err_loc: span.clone(),
+                    expected: ast::Mutability::Mutable,
+                    got: ast::Mutability::Immutable,
+                })
+                    .with_note(format!("`&{}` is only read through, write through a `*{}`", to, to)),
+            );
+        }
+    }
+
+    // the returned value must have the type the function is declared to return
+    fn check_return(&mut self, value: &Option<Box<ast::Expr>>, span: &Span) {
+        let got = match value {
+            Some(value) => match self.type_of(value) {
+                Some(got) => got,
+                // nothing to compare against
+                None => return,
+            },
+            None => ast::Type::Void,
+        };
+        self.check_returned(got, span);
+    }
+
+    // `got` is returned at `span`, by a `return` or as the value of the body
+    fn check_returned(&mut self, got: ast::Type, span: &Span) {
+        let Some((name, expected)) = self.function.clone() else {
+            return;
+        };
+        if compatible(&expected, &got) {
+            return;
+        }
+        if let Some(diag) = reference_mismatch(&expected, &got, span) {
+            self.diagnostics.push(diag.with_note(format!("`{}` is declared to return `{}`", name, expected)));
+            return;
+        }
+        self.diagnostics.push(
+            Diagnostic::from(Diag::TypeMismatch {
+                err_loc: span.clone(),
+                expected: Box::new(expected.clone()),
+                got: Box::new(got),
+            })
+                .with_note(format!("`{}` is declared to return `{}`", name, expected)),
+        );
+    }
+
+    fn lookup_type(&self, name: &str) -> Option<ast::Type> {
+        let local = self.scopes.iter().rev().find_map(|scope| scope.iter().rev().find(|l| l.name == name));
+        if let Some(local) = local {
+            return Some(local.var_type.clone());
+        }
+        self.params.get(name).or_else(|| self.globals.get(name)).cloned()
+    }
+
+    // the type of `expr` when it can be told from the declarations in scope
+    fn type_of(&self, expr: &ast::Expr) -> Option<ast::Type> {
+        types::type_of(self, expr)
+    }
+}
+
+impl types::Names for Checker {
+    fn variable_type(&self, name: &str) -> Option<ast::Type> {
+        self.lookup_type(name)
+    }
+
+    fn return_type(&self, name: &str) -> Option<ast::Type> {
+        self.functions.get(name).cloned()
+    }
+}
+
+// integers convert into each other and a `*T` into a `&T`, everything else must match exactly
+fn compatible(expected: &ast::Type, got: &ast::Type) -> bool {
+    match (expected, got) {
+        (ast::Type::RefType { to, .. }, ast::Type::PointerType { to: pointee }) => to == pointee,
+        _ => expected == got || (expected.is_integer() && got.is_integer()),
+    }
+}
+
+// a `&T` given where a `*T` is expected, it would allow writes through the reference
+fn reference_mismatch(expected: &ast::Type, got: &ast::Type, span: &Span) -> Option<Diagnostic> {
+    match (expected, got) {
+        (ast::Type::PointerType { to }, ast::Type::RefType { to: referent, mutable: false }) if to == referent => Some(
+            Diagnostic::from(Diag::ReferenceMutMismatch {
+                err_loc: span.clone(),
+                expected: ast::Mutability::Mutable,
+                got: ast::Mutability::Immutable,
+            }),
+        ),
+        _ => None,
+    }
+}
@@ -0,0 +1,47 @@
+use crate::parser::ast;
+
+// the declarations the type of an expression is told from.
+// the checker and the code generator each keep the names in scope their own way
+pub trait Names {
+    // the declared type of the variable or parameter `name`
+    fn variable_type(&self, name: &str) -> Option<ast::Type>;
+    // the return type of the function `name`
+    fn return_type(&self, name: &str) -> Option<ast::Type>;
+}
+
+// the type of `expr` when it can be told from the declarations in scope
+pub fn type_of(names: &impl Names, expr: &ast::Expr) -> Option<ast::Type> {
+    match expr {
+        ast::Expr::IntegerLiteral { ty, .. } => Some((**ty).clone()),
+        ast::Expr::StringLiteral(_) => Some(*ast::Type::new_pointer(ast::Type::new_char())),
+        ast::Expr::CharLiteral(_) => Some(ast::Type::Char),
+        ast::Expr::Identifier { name, .. } | ast::Expr::Variable(name) => names.variable_type(name),
+        ast::Expr::Call { func, .. } => match func.as_ref() {
+            ast::Expr::Identifier { name, .. } | ast::Expr::Variable(name) => names.return_type(name),
+            _ => None,
+        },
+        ast::Expr::UnaryOp { op: ast::UnaryOperator::Deref, expr, .. } => match type_of(names, expr)? {
+            ast::Type::PointerType { to } | ast::Type::RefType { to, .. } => Some(*to),
+            _ => None,
+        },
+        ast::Expr::UnaryOp { op: ast::UnaryOperator::AddrOf, expr, .. } => {
+            Some(*ast::Type::new_pointer(Box::new(type_of(names, expr)?)))
+        }
+        ast::Expr::UnaryOp { op: ast::UnaryOperator::Not, .. } => Some(ast::Type::Int),
+        ast::Expr::UnaryOp { expr, .. } => type_of(names, expr),
+        ast::Expr::BinaryOp { op, lhs, rhs } => match op {
+            ast::BinaryOperator::Eq | ast::BinaryOperator::Neq
+            | ast::BinaryOperator::Lt | ast::BinaryOperator::Gt
+            | ast::BinaryOperator::Leq | ast::BinaryOperator::Geq
+            | ast::BinaryOperator::And | ast::BinaryOperator::Or => Some(ast::Type::Int),
+            ast::BinaryOperator::Assign => type_of(names, lhs),
+            // arithmetic happens in the type of the lhs, pointer arithmetic is left alone
+            _ => {
+                let lhs = type_of(names, lhs)?;
+                if lhs.is_integer() && type_of(names, rhs)?.is_integer() { Some(lhs) } else { None }
+            }
+        },
+        ast::Expr::CompoundAssign { target, .. } => type_of(names, target),
+        _ => None,
+    }
+}
@@ -0,0 +1,46 @@
+mod common;
+
+use common::{errors, ir};
+
+// the values stored by the IR lines starting with `store`, like `store a.`
+fn stored(ir: &str, store: &str) -> Vec<String> {
+    ir.lines()
+        .map(str::trim)
+        .filter(|line| line.starts_with(store))
+        .map(|line| line.rsplit(", ").next().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn assignment_is_right_associative() {
+    let ir = ir("chain.cx", "fn main() -> int {\n    var a: int = 0;\n    var b: int = 0;\n    a = b = 3;\n    a\n}\n");
+    // `b = 3` happens first and its value is what `a` gets
+    let lines: Vec<&str> = ir.lines().map(str::trim).collect();
+    let to_b = lines.iter().rposition(|line| line.starts_with("store b.")).unwrap();
+    assert!(lines[to_b + 1].starts_with("store a."), "got:\n{}", ir);
+    assert_eq!(stored(&ir, "store a.")[1], stored(&ir, "store b.")[1], "got:\n{}", ir);
+}
+
+#[test]
+fn compound_assignment_operates_and_stores() {
+    let ir = ir("compound.cx", "fn main() -> int {\n    var a: int = 1;\n    a += 2;\n    a <<= a;\n    a\n}\n");
+    let lines: Vec<&str> = ir.lines().map(str::trim).collect();
+    for op in ["add", "shl"] {
+        // the result of the operation on the loaded `a` is stored back into it
+        let at = lines.iter().position(|line| line.contains(&format!(" = {} %", op))).unwrap_or_else(|| panic!("got:\n{}", ir));
+        let result = lines[at].split(' ').next().unwrap();
+        assert_eq!(lines[at + 1], format!("store a.0, {}", result), "got:\n{}", ir);
+    }
+    assert_eq!(stored(&ir, "store a.").len(), 3, "got:\n{}", ir);
+}
+
+#[test]
+fn only_places_can_be_assigned() {
+    let stderr = errors("lvalues.cx", "fn main() -> int {\n    var a: int = 0;\n    5 = a;\n    a + 1 = 2;\n    5 += 1;\n    a = 1;\n    a\n}\n");
+    assert!(stderr.contains("error[E0015]: the operand of `=` is not assignable\n"), "got:\n{}", stderr);
+    assert!(stderr.contains("lvalues.cx:3:5\n"), "got:\n{}", stderr);
+    // the whole operand is underlined
+    assert!(stderr.contains("4 |     a + 1 = 2;\n  |     ^^^^^\n"), "got:\n{}", stderr);
+    assert!(stderr.contains("error[E0015]: the operand of `+=` is not assignable\n"), "got:\n{}", stderr);
+    assert_eq!(stderr.matches("error[").count(), 3, "got:\n{}", stderr);
+}
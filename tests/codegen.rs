@@ -0,0 +1,125 @@
+mod common;
+
+use common::asm;
+
+#[test]
+fn global_initializers_are_folded() {
+    let asm = asm("globals.cx", "var s: *char = \"hi\";\nvar g: int = 1 + 2 * -3;\nvar z: int;\n");
+    assert!(asm.contains("s: dq str_0\n"), "got:\n{}", asm);
+    assert!(asm.contains("str_0: db \"hi\", 0\n"), "got:\n{}", asm);
+    assert!(asm.contains("g: dq -5\n"), "got:\n{}", asm);
+    assert!(asm.contains("z: resq 1\n"), "got:\n{}", asm);
+}
+
+// the lines of the function `name`, up to its `ret`
+fn function<'a>(asm: &'a str, name: &str) -> Vec<&'a str> {
+    let mut lines = asm.lines().map(str::trim).skip_while(|line| *line != format!("{}:", name));
+    assert!(lines.next().is_some(), "no {} in:\n{}", name, asm);
+    lines.take_while(|line| !line.starts_with("ret")).collect()
+}
+
+// the `[rbp - n]` slot a `mov` stores `src` into, for every such store in order
+fn stores<'a>(lines: &[&'a str], src: &str) -> Vec<&'a str> {
+    let suffix = format!("], {}", src);
+    lines.iter()
+        .filter_map(|line| line.strip_prefix("mov [")?.strip_suffix(suffix.as_str()))
+        .filter(|slot| slot.starts_with("rbp - "))
+        .collect()
+}
+
+// the 8-byte values on the stack and in the argument registers when `call` runs,
+// following the `mov rax, n`, `push`es and loads of the caller
+fn call_site(lines: &[&str]) -> (Vec<i64>, Vec<(String, i64)>) {
+    let mut stack: Vec<i64> = Vec::new();
+    let mut registers: Vec<(String, i64)> = Vec::new();
+    let mut rax = 0;
+    // the value `[rsp + offset]` addresses, the top of the stack is at `rsp + 0`
+    let at = |stack: &Vec<i64>, offset: &str| stack[stack.len() - 1 - offset.parse::<usize>().unwrap() / 8];
+    for line in lines.iter().take_while(|line| !line.starts_with("call ")) {
+        if let Some(value) = line.strip_prefix("mov rax, ").and_then(|v| v.parse().ok()) {
+            rax = value;
+        } else if *line == "push rax" {
+            stack.push(rax);
+        } else if let Some(offset) = line.strip_prefix("push qword [rsp + ").and_then(|l| l.strip_suffix(']')) {
+            stack.push(at(&stack, offset));
+        } else if let Some((register, offset)) = line.strip_prefix("mov ").and_then(|l| l.split_once(", [rsp + "))
+            && let Some(offset) = offset.strip_suffix(']') {
+            registers.push((register.to_string(), at(&stack, offset)));
+        }
+    }
+    (stack, registers)
+}
+
+#[test]
+fn arguments_past_the_sixth_go_on_the_stack() {
+    let asm = asm(
+        "stack_args.cx",
+        "fn f(a: int, b: int, c: int, d: int, e: int, g: int, h: int, i: int) -> int { h - i }\n\
+         fn main() -> int { f(1, 2, 3, 4, 5, 6, 7, 8) }\n",
+    );
+    // the callee spills its seventh and eighth parameters from above the return address,
+    // and `h - i` reads them back from where they were spilled
+    let callee = function(&asm, "f");
+    let spilled = stores(&callee, "rax");
+    assert_eq!(spilled.len(), 2, "got:\n{}", asm);
+    for (i, slot) in spilled.iter().enumerate() {
+        let load = callee.iter().position(|line| *line == format!("mov rax, [rbp + {}]", 16 + 8 * i)).unwrap();
+        assert_eq!(callee[load + 1], format!("mov [{}], rax", slot), "got:\n{}", asm);
+    }
+    let reads: Vec<&str> = callee.iter().filter_map(|line| line.strip_prefix("mov rax, [")?.strip_suffix(']')).collect();
+    assert_eq!(reads[reads.len() - 2..], spilled[..], "got:\n{}", asm);
+
+    // the caller leaves the seventh argument on top of the stack and the eighth above it,
+    // the first six go in registers
+    let caller = function(&asm, "main");
+    let (stack, registers) = call_site(&caller);
+    assert_eq!(stack[stack.len() - 2..], [8, 7], "got:\n{}", asm);
+    let expected: Vec<(String, i64)> = ["rdi", "rsi", "rdx", "rcx", "r8", "r9"]
+        .iter()
+        .zip(1..)
+        .map(|(register, value)| (register.to_string(), value))
+        .collect();
+    assert_eq!(registers, expected, "got:\n{}", asm);
+    // rsp stays 16-byte aligned for the call and everything pushed is popped after it
+    assert_eq!(stack.len() % 2, 0, "got:\n{}", asm);
+    let call = caller.iter().position(|line| *line == "call f").unwrap();
+    assert_eq!(caller[call + 1], format!("add rsp, {}", 8 * stack.len()), "got:\n{}", asm);
+}
+
+#[test]
+fn exact_width_integers_are_sized_and_signed() {
+    let asm = asm(
+        "widths.cx",
+        "var g: i16;\n\
+         fn f(p: *i32, n: u64) -> int {\n    var x: u8 = 255u8;\n    x = x + 1;\n    g = -1i16;\n    *p = *p >> 1;\n    (n / 3 < n >> 2) + x + g\n}\n",
+    );
+    // narrow values live in slots of their own size and wrap around when stored
+    let lines = function(&asm, "f");
+    let slot = stores(&lines, "al");
+    assert!(!slot.is_empty(), "got:\n{}", asm);
+    assert!(lines.contains(&format!("movzx eax, byte [{}]", slot[0]).as_str()), "got:\n{}", asm);
+    assert!(asm.contains("    add rax, r11\n    movzx eax, al\n"), "got:\n{}", asm);
+    assert!(asm.contains("    mov [r11], ax\n    movsx rax, ax\n"), "got:\n{}", asm);
+    assert!(asm.contains("    movsx rax, word [rel g]\n"), "got:\n{}", asm);
+    assert!(asm.contains("g: resw 1\n"), "got:\n{}", asm);
+    // i32 pointees are 4 bytes and shift arithmetically
+    assert!(asm.contains("    movsxd rax, dword [rax]\n"), "got:\n{}", asm);
+    assert!(asm.contains("    sar rax, cl\n    movsxd rax, eax\n"), "got:\n{}", asm);
+    assert!(asm.contains("    mov [r11], eax\n"), "got:\n{}", asm);
+    // u64 operands divide, shift and compare unsigned
+    assert!(asm.contains("    xor edx, edx\n    div rcx\n"), "got:\n{}", asm);
+    assert!(asm.contains("    shr rax, cl\n"), "got:\n{}", asm);
+    assert!(asm.contains("    setb al\n"), "got:\n{}", asm);
+}
+
+#[test]
+fn every_binary_operator_compiles() {
+    let asm = asm(
+        "operators.cx",
+        "fn f(a: int, b: int) -> int {\n    var x: int = a + b - a * b / a % b;\n    x = (a & b) | (a ^ b) << 1 >> 2;\n    \
+         x += (a == b) + (a != b) + (a < b) + (a > b) + (a <= b) + (a >= b);\n    a && b || x\n}\n",
+    );
+    for inst in ["add", "sub", "imul", "idiv", "and", "or", "xor", "shl", "sar", "sete", "setne", "setl", "setg", "setle", "setge"] {
+        assert!(asm.contains(&format!("    {} ", inst)), "no {} in:\n{}", inst, asm);
+    }
+}
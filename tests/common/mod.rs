@@ -0,0 +1,48 @@
+// running the compiler on a source file, shared by the integration tests.
+// every test binary uses only some of these
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+// where a test writes the source file called `name`
+pub fn path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name)
+}
+
+// writes `source` to the file `name` and runs cx on it with `args`
+pub fn compile(name: &str, source: &str, args: &[&str]) -> Output {
+    let path = path(name);
+    fs::write(&path, source).unwrap();
+    Command::new(env!("CARGO_BIN_EXE_cx"))
+        .arg("--color=never")
+        .args(args)
+        .arg(&path)
+        .output()
+        .unwrap()
+}
+
+// what was reported for `source`, which must fail to compile
+pub fn errors(name: &str, source: &str) -> String {
+    let output = compile(name, source, &["--emit=ir"]);
+    assert!(!output.status.success(), "{} compiled without errors", name);
+    String::from_utf8(output.stderr).unwrap()
+}
+
+// the IR of `source`, which must compile cleanly
+pub fn ir(name: &str, source: &str) -> String {
+    emit(name, source, "--emit=ir")
+}
+
+// the assembly of `source`, which must compile cleanly
+pub fn asm(name: &str, source: &str) -> String {
+    emit(name, source, "--emit=asm")
+}
+
+fn emit(name: &str, source: &str, emit: &str) -> String {
+    let output = compile(name, source, &[emit]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success() && stderr.is_empty(), "{} did not compile cleanly:\n{}", name, stderr);
+    String::from_utf8(output.stdout).unwrap()
+}
@@ -0,0 +1,385 @@
+mod common;
+
+use common::{asm, errors, ir};
+
+// the lines of the block that starts at `label`, up to the next label
+fn block<'a>(code: &'a str, label: &str) -> Vec<&'a str> {
+    let start = format!("{}:", label);
+    let mut lines = code.lines().map(str::trim).skip_while(|line| *line != start);
+    assert!(lines.next().is_some(), "no {} in:\n{}", label, code);
+    lines.take_while(|line| !line.ends_with(':')).collect()
+}
+
+// the labels a `br`, `jmp`, `je` or `ja` can go to, the taken branch of a `br` first
+fn targets(line: &str) -> Vec<&str> {
+    match line.trim().split_once(' ') {
+        Some(("br", operands)) => operands.split(", ").skip(1).collect(),
+        Some(("jmp" | "je" | "ja", label)) => vec![label],
+        _ => vec![],
+    }
+}
+
+// every `br` of the IR, in order
+fn branches(ir: &str) -> Vec<&str> {
+    ir.lines().map(str::trim).filter(|line| line.starts_with("br ")).collect()
+}
+
+// the label defined with `prefix`, like `.for_end_4` for `.for_end_`
+fn label<'a>(asm: &'a str, prefix: &str) -> &'a str {
+    asm.lines()
+        .find_map(|line| line.strip_suffix(':').filter(|label| label.starts_with(prefix)))
+        .unwrap_or_else(|| panic!("no {} label in:\n{}", prefix, asm))
+}
+
+#[test]
+fn if_else_yields_the_value_of_the_arm_taken() {
+    let ir = ir("if_value.cx", "fn sign(n: int) -> int {\n    if n < 0 { 0 - 1 } else { 1 }\n}\n");
+    let arms = targets(branches(&ir)[0]);
+    let (then_arm, else_arm) = (block(&ir, arms[0]), block(&ir, arms[1]));
+    // both arms store into the one slot of the `if`, then meet where it is loaded
+    let (then_store, else_store) = (then_arm[then_arm.len() - 2], else_arm[else_arm.len() - 2]);
+    assert!(then_store.starts_with("store if."), "got:\n{}", ir);
+    assert_eq!(then_store.split(',').next(), else_store.split(',').next(), "got:\n{}", ir);
+    let end = targets(then_arm[then_arm.len() - 1]);
+    assert_eq!(end, targets(else_arm[else_arm.len() - 1]), "got:\n{}", ir);
+    let end = block(&ir, end[0]);
+    assert!(end[0].contains(" = load if.") && end[1].starts_with("ret %"), "got:\n{}", ir);
+}
+
+#[test]
+fn if_without_else_has_no_value() {
+    let ir = ir("if_stmt.cx", "fn clamp(n: int) -> int {\n    if n < 0 { n = 0; }\n    n\n}\n");
+    assert!(!ir.contains("if."), "got:\n{}", ir);
+    // the missing else goes straight to where the taken arm ends up
+    let arms = targets(branches(&ir)[0]);
+    let then_arm = block(&ir, arms[0]);
+    assert_eq!(block(&ir, arms[1]), [then_arm[then_arm.len() - 1]], "got:\n{}", ir);
+}
+
+#[test]
+fn a_statement_ends_with_its_if() {
+    // `*p = 1` after the block is a new statement, not a product with the `if`
+    let ir = ir("if_deref.cx", "fn f(p: *int, c: int) -> int {\n    if c { 0; }\n    *p = 1;\n    0\n}\n");
+    assert!(ir.contains("store [%"), "got:\n{}", ir);
+    assert!(!ir.contains("mul"), "got:\n{}", ir);
+}
+
+#[test]
+fn loops_jump_to_their_own_labels() {
+    let source = "\
+fn count(n: int) -> int {
+    var total: int = 0;
+    for var i: int = 0; i < n; i += 1 {
+        while 1 {
+            break;
+        }
+        if i == 2 { continue; }
+        total += i;
+    }
+    total
+}
+";
+    let ir = ir("loops.cx", source);
+    let [for_branch, while_branch, if_branch] = branches(&ir)[..] else { panic!("got:\n{}", ir) };
+    // the inner `break` leaves the while
+    let while_targets = targets(while_branch);
+    assert_eq!(targets(block(&ir, while_targets[0])[0]), [while_targets[1]], "got:\n{}", ir);
+    // the `continue` goes to the for's step, which goes back to its condition
+    let step = block(&ir, targets(block(&ir, targets(if_branch)[0])[0])[0]);
+    assert!(step.iter().any(|line| line.starts_with("store i.")), "got:\n{}", ir);
+    assert_eq!(block(&ir, targets(step[step.len() - 1])[0]).last(), Some(&for_branch), "got:\n{}", ir);
+
+    // the same jumps in the assembly
+    let asm = asm("loops.cx", source);
+    let (while_end, for_step) = (label(&asm, ".while_end_"), label(&asm, ".for_step_"));
+    let while_body = block(&asm, label(&asm, ".while_cond_"));
+    assert!(while_body.contains(&format!("je {}", while_end).as_str()), "got:\n{}", asm);
+    assert!(while_body.contains(&format!("jmp {}", while_end).as_str()), "got:\n{}", asm);
+    assert!(block(&asm, while_end).contains(&format!("jmp {}", for_step).as_str()), "got:\n{}", asm);
+    let step = block(&asm, for_step);
+    assert_eq!(step[step.len() - 1], format!("jmp {}", label(&asm, ".for_cond_")), "got:\n{}", asm);
+}
+
+#[test]
+fn break_and_continue_need_a_loop() {
+    let stderr = errors("no_loop.cx", "fn main() -> int {\n    if 1 { break; }\n    continue;\n    0\n}\n");
+    assert!(stderr.contains("error[E0022]: `break` outside of a loop\n"), "got:\n{}", stderr);
+    assert!(stderr.contains("error[E0022]: `continue` outside of a loop\n"), "got:\n{}", stderr);
+}
+
+#[test]
+fn labeled_jumps_leave_the_named_loop() {
+    let source = "\
+fn find(n: int) -> int {
+    var found: int = 0;
+    'rows: for var i: int = 0; i < n; i += 1 {
+        'cols: while 1 {
+            if i == 3 { break 'rows; }
+            continue 'rows;
+        }
+    }
+    found
+}
+";
+    let ir = ir("labeled.cx", source);
+    let [for_branch, while_branch, if_branch] = branches(&ir)[..] else { panic!("got:\n{}", ir) };
+    let (for_end, while_end) = (targets(for_branch)[1], targets(while_branch)[1]);
+    // `break 'rows` passes over the end of the while to the end of the for
+    let taken = block(&ir, targets(if_branch)[0]);
+    assert_eq!(targets(taken[0]), [for_end], "got:\n{}", ir);
+    // `continue 'rows` goes to the for's step, which the while also ends in
+    let after_if = block(&ir, targets(taken[1])[0]);
+    assert_eq!(targets(after_if[0]), targets(block(&ir, while_end)[0]), "got:\n{}", ir);
+    assert!(block(&ir, targets(after_if[0])[0]).iter().any(|line| line.starts_with("store i.")), "got:\n{}", ir);
+
+    // the same jumps in the assembly
+    let asm = asm("labeled.cx", source);
+    let while_body = block(&asm, label(&asm, ".while_cond_"));
+    assert!(while_body.contains(&format!("jmp {}", label(&asm, ".for_end_")).as_str()), "got:\n{}", asm);
+    assert!(!while_body.contains(&format!("jmp {}", label(&asm, ".while_end_")).as_str()), "got:\n{}", asm);
+    assert_eq!(block(&asm, label(&asm, ".if_end_"))[0], format!("jmp {}", label(&asm, ".for_step_")), "got:\n{}", asm);
+}
+
+#[test]
+fn labels_must_name_an_enclosing_loop() {
+    let source = "fn main() -> int {\n    'a: while 1 {\n        'a: while 1 { break 'b; }\n    }\n    0\n}\n";
+    let stderr = errors("bad_labels.cx", source);
+    assert!(stderr.contains("error[E0024]: label `'a` shadows a label of an enclosing loop\n"), "got:\n{}", stderr);
+    assert!(stderr.contains("-- `'a` is already the label of this loop\n"), "got:\n{}", stderr);
+    assert!(stderr.contains("error[E0023]: use of undeclared label `'b`\n"), "got:\n{}", stderr);
+}
+
+#[test]
+fn every_return_jumps_to_the_one_epilogue() {
+    let asm = asm("returns.cx", "fn abs(n: int) -> int {\n    if n < 0 { return 0 - n; }\n    return n;\n}\n");
+    let function = &asm[asm.find("abs:").unwrap()..];
+    let epilogue = label(function, ".return_");
+    assert_eq!(function.matches(&format!("jmp {}\n", epilogue)).count(), 2, "got:\n{}", function);
+    assert_eq!(function.matches("    ret").count(), 1, "got:\n{}", function);
+    assert_eq!(block(function, epilogue)[0], "mov rsp, rbp", "got:\n{}", function);
+}
+
+#[test]
+fn returned_values_must_match_the_return_type() {
+    let source = "\
+fn name() -> int {
+    return \"cx\";
+}
+fn value() -> *char {
+    return;
+}
+fn narrow(p: *int) -> u8 {
+    return *p + 1;
+}
+";
+    let stderr = errors("bad_returns.cx", source);
+    assert!(stderr.contains("error[E0018]: mismatched types, expected `int` but found `*char`\n"), "got:\n{}", stderr);
+    assert!(stderr.contains("= note: `name` is declared to return `int`\n"), "got:\n{}", stderr);
+    assert!(stderr.contains("error[E0018]: mismatched types, expected `*char` but found `void`\n"), "got:\n{}", stderr);
+    // integers convert into each other
+    assert_eq!(stderr.matches("error[").count(), 2, "got:\n{}", stderr);
+}
+
+#[test]
+fn bodies_must_end_in_a_value_of_the_return_type() {
+    let source = "\
+fn name() -> int { \"cx\" }
+fn empty() -> int { }
+fn partial(c: int) -> int {
+    if c { return 1; }
+}
+fn either(c: int) -> int { if c { return 1; } else { 2 } }
+fn forever() -> int { for ;; { return 3; } }
+fn discarded() { 4 }
+";
+    let stderr = errors("bad_tails.cx", source);
+    assert!(stderr.contains("error[E0018]: mismatched types, expected `int` but found `*char`\n --> "), "got:\n{}", stderr);
+    assert!(stderr.contains("bad_tails.cx:1:20\n"), "got:\n{}", stderr);
+    assert!(stderr.contains("error[E0030]: `empty` can reach the end of its body without returning a value\n"), "got:\n{}", stderr);
+    assert!(stderr.contains("bad_tails.cx:2:21\n"), "got:\n{}", stderr);
+    assert!(stderr.contains("bad_tails.cx:5:1\n"), "got:\n{}", stderr);
+    assert!(stderr.contains("= note: `partial` is declared to return `int`\n"), "got:\n{}", stderr);
+    // arms that return, loops that never end and void functions are fine
+    assert_eq!(stderr.matches("error[").count(), 3, "got:\n{}", stderr);
+}
+
+#[test]
+fn switch_arms_get_their_own_blocks() {
+    let source = "\
+fn f(n: int) -> int {
+    switch n {
+        case 1, 2 { 10 }
+        case 3 { fallthrough; }
+        default { 0 }
+    }
+}
+";
+    let ir = ir("switch.cx", source);
+    let switch = ir.lines().map(str::trim).find(|line| line.starts_with("switch ")).unwrap();
+    let (cases, default) = switch.split_once("], ").unwrap();
+    let cases: Vec<(&str, &str)> = cases.split_once('[').unwrap().1.split(", ").map(|case| case.split_once(": ").unwrap()).collect();
+    assert_eq!(cases.iter().map(|case| case.0).collect::<Vec<_>>(), ["1", "2", "3"], "got:\n{}", ir);
+    // the values of one case share its block
+    assert_eq!(cases[0].1, cases[1].1, "got:\n{}", ir);
+    assert_ne!(cases[1].1, cases[2].1, "got:\n{}", ir);
+    // `fallthrough` continues with the default arm written after it
+    assert_eq!(targets(block(&ir, cases[2].1)[0]), [default], "got:\n{}", ir);
+    // the falling arm jumps away instead of leaving a value, the switch still has one
+    assert!(ir.contains(" = load switch.") && !ir.ends_with("    ret\n"), "got:\n{}", ir);
+}
+
+#[test]
+fn dense_switches_use_a_jump_table() {
+    let asm = asm("dense.cx", "\
+fn f(n: int) -> int {
+    switch n {
+        case 1 { 10 }
+        case 2, 3 { 20 }
+        case 5 { 50 }
+        default { 0 }
+    }
+}
+");
+    assert!(asm.contains("    mov r11, 1\n    sub rax, r11\n    cmp rax, 4\n"), "got:\n{}", asm);
+    // the hole at 4 goes to the default arm
+    let entries: Vec<&str> = asm.lines().filter(|l| l.starts_with("    dd ")).collect();
+    assert_eq!(entries.len(), 5, "got:\n{}", asm);
+    assert_eq!(entries[1], entries[2]);
+    let default = asm.lines().find_map(|line| line.strip_prefix("    ja ")).unwrap();
+    assert!(entries[3].starts_with(&format!("    dd {} - ", default)), "got:\n{}", asm);
+    assert_eq!(block(&asm, default)[0], "mov rax, 0", "got:\n{}", asm);
+}
+
+#[test]
+fn sparse_switches_compare_each_value() {
+    let asm = asm("sparse.cx", "\
+fn f(n: int) -> int {
+    switch n {
+        case -1000 { 1 }
+        case 7 { 2 }
+        case 99999999999 { 3 }
+        default { 0 }
+    }
+}
+");
+    assert!(!asm.contains("    dd "), "got:\n{}", asm);
+    // each value is compared in turn and jumps to the block of its arm
+    let arm = |compare: &str| {
+        let at = asm.find(compare).unwrap_or_else(|| panic!("no {:?} in:\n{}", compare, asm));
+        let jump = asm[at + compare.len()..].lines().next().unwrap();
+        block(&asm, targets(jump)[0])[0]
+    };
+    assert_eq!(arm("    cmp rax, -1000\n"), "mov rax, 1", "got:\n{}", asm);
+    assert_eq!(arm("    cmp rax, 7\n"), "mov rax, 2", "got:\n{}", asm);
+    // too wide for an immediate
+    assert_eq!(arm("    mov r11, 99999999999\n    cmp rax, r11\n"), "mov rax, 3", "got:\n{}", asm);
+    // anything else goes to the default arm
+    let default = asm.lines().find(|line| line.starts_with("    jmp ")).unwrap();
+    assert_eq!(block(&asm, targets(default)[0])[0], "mov rax, 0", "got:\n{}", asm);
+}
+
+#[test]
+fn switch_cases_must_be_distinct() {
+    let source = "\
+fn f(n: int) -> int {
+    switch n {
+        case 1, 2 { 0; }
+        case 2 { 0; }
+        default { 0; }
+        default { fallthrough; }
+    }
+    0
+}
+";
+    let stderr = errors("bad_switch.cx", source);
+    assert!(stderr.contains("error[E0025]: duplicate `case 2` in switch\n"), "got:\n{}", stderr);
+    assert!(stderr.contains("- `case 2` first appears here\n"), "got:\n{}", stderr);
+    assert!(stderr.contains("error[E0025]: duplicate `default` in switch\n"), "got:\n{}", stderr);
+    assert!(stderr.contains("error[E0026]: `fallthrough` has no following switch arm\n"), "got:\n{}", stderr);
+}
+
+#[test]
+fn switches_need_an_integer() {
+    let source = "\
+fn f(s: *char) -> int {
+    switch s {
+        case 0 { 1 }
+        default { 2 }
+    }
+}
+";
+    let stderr = errors("pointer_switch.cx", source);
+    assert!(stderr.contains("error[E0021]: `switch` cannot be applied to `*char`\n"), "got:\n{}", stderr);
+    assert!(stderr.contains("pointer_switch.cx:2:12\n"), "got:\n{}", stderr);
+    assert_eq!(stderr.matches("error[").count(), 1, "got:\n{}", stderr);
+}
+
+#[test]
+fn enums_are_switched_on_their_variants() {
+    let ir = ir("enum_switch.cx", "\
+enum Color {
+    Red,
+    Green = 4,
+    Blue,
+}
+
+fn f(c: Color) -> int {
+    switch c {
+        case Color::Red { 1 }
+        case Color::Green, Color::Blue { 2 }
+        default { 0 }
+    }
+}
+
+fn main() -> int {
+    f(Color::Blue)
+}
+");
+    // variants without a value follow the one before them
+    let switch = ir.lines().map(str::trim).find(|line| line.starts_with("switch ")).unwrap();
+    assert!(switch.contains("[0: L0, 4: L1, 5: L1]"), "got:\n{}", ir);
+    assert!(ir.contains("const 5\n"), "got:\n{}", ir);
+}
+
+#[test]
+fn enum_cases_must_be_variants_of_the_scrutinee() {
+    let source = "\
+enum Color { Red, Green }
+enum Shape { Circle, Square }
+
+fn f(c: Color, n: int) -> int {
+    switch c {
+        case Color::Red, Shape::Circle { 1 }
+        case 2 { 2 }
+        default { 0 }
+    }
+    switch n {
+        case Color::Green { 0 }
+        default { 1 }
+    }
+}
+";
+    let stderr = errors("enum_cases.cx", source);
+    assert!(stderr.contains("error[E0018]: mismatched types, expected `Color` but found `Shape`\n"), "got:\n{}", stderr);
+    assert!(stderr.contains("error[E0018]: mismatched types, expected `Color` but found `int`\n"), "got:\n{}", stderr);
+    assert!(stderr.contains("error[E0018]: mismatched types, expected `int` but found `Color`\n"), "got:\n{}", stderr);
+    for location in ["6:26", "7:14", "11:14"] {
+        assert!(stderr.contains(&format!("enum_cases.cx:{}\n", location)), "expected an error at {}, got:\n{}", location, stderr);
+    }
+}
+
+#[test]
+fn variants_must_be_declared() {
+    let source = "\
+enum Color { Red, Green, Red }
+
+fn f() -> Color {
+    Color::Blue
+}
+";
+    let stderr = errors("variants.cx", source);
+    assert!(stderr.contains("error[E0017]: `Color::Red` is defined twice\n"), "got:\n{}", stderr);
+    assert!(stderr.contains("variants.cx:1:26\n"), "got:\n{}", stderr);
+    assert!(stderr.contains("error[E0016]: `Color::Blue` is not defined\n"), "got:\n{}", stderr);
+    assert!(stderr.contains("variants.cx:4:5\n"), "got:\n{}", stderr);
+}
@@ -0,0 +1,171 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+const MAIN: &str = "fn main() -> int { 0 }\n";
+
+// runs cx with `args` and `stdin` as its standard input
+fn cx(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_cx"))
+        .arg("--color=never")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn the_source_can_come_from_stdin() {
+    let output = cx(&["--emit=ir", "-"], MAIN);
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout).unwrap().starts_with("fn @main():\n"));
+}
+
+#[test]
+fn o_writes_the_output_to_a_file() {
+    let (apart, joined) = (common::path("o_apart.ir"), common::path("o_joined.ir"));
+    // `-o <path>` and `-o<path>`
+    let joined_flag = format!("-o{}", joined.display());
+    let cases = [(&apart, vec!["-o", apart.to_str().unwrap()]), (&joined, vec![joined_flag.as_str()])];
+    for (out, flags) in cases {
+        let _ = fs::remove_file(out);
+        let output = common::compile("o.cx", MAIN, &[&["--emit=ir"], &flags[..]].concat());
+        assert!(output.status.success() && output.stdout.is_empty(), "{:?}", output);
+        assert!(fs::read_to_string(out).unwrap().starts_with("fn @main():\n"), "{}", out.display());
+    }
+}
+
+#[test]
+fn each_stage_stops_after_itself() {
+    let stages = [
+        ("tokens", "fn\nidentifier(main)\n("),
+        ("ast", "[\n    FunctionDecl {\n        name: \"main\","),
+        ("ir", "fn @main():\n"),
+        ("asm", "global main\n"),
+    ];
+    for (stage, start) in stages {
+        let output = common::compile("stages.cx", MAIN, &[&format!("--emit={}", stage)]);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(output.status.success() && output.stderr.is_empty(), "{}: {:?}", stage, output.stderr);
+        assert!(stdout.starts_with(start), "{} got:\n{}", stage, stdout);
+    }
+}
+
+#[test]
+fn unknown_stages_are_usage_errors() {
+    let output = cx(&["--emit=bin", "x.cx"], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr).unwrap().starts_with("cx: error: unknown emit stage 'bin'\n"));
+}
+
+#[test]
+fn tokens_are_not_written_after_lexer_errors() {
+    let output = cx(&["--emit=tokens", "-"], "fn main() -> int { $ }\n");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr.starts_with("error[E0001]: invalid character '$'\n"), "got:\n{}", stderr);
+    assert!(output.stdout.is_empty(), "got:\n{}", String::from_utf8_lossy(&output.stdout));
+}
+
+#[test]
+fn usage_errors_exit_with_2() {
+    let cases: [(&[&str], &str); 4] = [
+        (&["--bogus", "x.cx"], "unknown option '--bogus'"),
+        (&[], "no input files"),
+        (&["x.cx", "-o"], "'-o' expects an output path"),
+        (&["-", "-"], "stdin ('-') can only be given once"),
+    ];
+    for (args, message) in cases {
+        let output = cx(args, "");
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+        assert!(stderr.starts_with(&format!("cx: error: {}\n\nusage: cx", message)), "got:\n{}", stderr);
+    }
+}
+
+#[test]
+fn failures_exit_with_1() {
+    let output = cx(&["missing.cx"], "");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr.starts_with("cx: error: could not read missing.cx:"), "got:\n{}", stderr);
+
+    let output = cx(&["--emit=ir", "-"], "fn main() -> int { x }\n");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr.contains(" --> <stdin>:1:20\n"), "got:\n{}", stderr);
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn help_goes_to_stdout() {
+    let output = cx(&["-h"], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert!(String::from_utf8(output.stdout).unwrap().starts_with("usage: cx"));
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn write_errors_are_reported() {
+    let output = common::compile("full.cx", MAIN, &["--emit=ir", "-o", "/dev/full"]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(!output.status.success());
+    assert!(stderr.starts_with("cx: error: could not write /dev/full:"), "got:\n{}", stderr);
+}
+
+// the stand-in assembler and linker of tests/tools
+#[cfg(unix)]
+fn tool(name: &str) -> String {
+    format!("{}/tests/tools/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+// what a stand-in tool was called with to write `output`
+#[cfg(unix)]
+fn tool_args(output: &Path) -> String {
+    fs::read_to_string(format!("{}.args", output.display())).unwrap()
+}
+
+#[test]
+#[cfg(unix)]
+fn missing_tools_are_reported() {
+    for (flag, role) in [("--assembler", "assembler"), ("--linker", "linker")] {
+        let tools = [format!("--assembler={}", tool("stand-in")), format!("{}=/nonexistent", flag)];
+        let exe = common::path(&format!("missing_{}", role));
+        let output = common::compile("missing_tool.cx", MAIN, &[&tools[0], &tools[1], "-o", exe.to_str().unwrap()]);
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert_eq!(output.status.code(), Some(1));
+        let message = format!("cx: error: {} '/nonexistent' not found, install it or pass {}=<path>\n", role, flag);
+        assert_eq!(stderr, message);
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn objects_and_executables_go_through_the_configured_tools() {
+    let assembler = format!("--assembler={}", tool("stand-in"));
+    let obj = common::path("tools.o");
+    let output = common::compile("tools.cx", MAIN, &["--emit=obj", &assembler, "-o", obj.to_str().unwrap()]);
+    assert!(output.status.success(), "{:?}", output);
+    let args = tool_args(&obj);
+    assert!(args.starts_with("-f elf64 ") && args.ends_with(&format!(".asm -o {}\n", obj.display())), "got: {}", args);
+    // the stand-in copies, so the object is the assembly
+    assert!(fs::read_to_string(&obj).unwrap().contains("global main\n"));
+
+    // a compiler driver brings libc and its `_start`, a plain `ld` needs the generated one
+    for (linker, flags, start) in [("stand-in", "-no-pie ", false), ("ld", "", true)] {
+        let exe = common::path(&format!("tools_{}", linker));
+        let linker_flag = format!("--linker={}", tool(linker));
+        let output = common::compile("tools.cx", MAIN, &[&assembler, &linker_flag, "-o", exe.to_str().unwrap()]);
+        assert!(output.status.success(), "{:?}", output);
+        let args = tool_args(&exe);
+        assert!(args.starts_with(flags) && args.ends_with(&format!("out.o -o {}\n", exe.display())), "got: {}", args);
+        assert_eq!(fs::read_to_string(&exe).unwrap().contains("_start:\n"), start, "{}", linker);
+    }
+}
@@ -0,0 +1,137 @@
+mod common;
+
+use common::errors;
+
+fn assert_reported_at(name: &str, source: &str, location: &str) {
+    let stderr = errors(name, source);
+    let expected = format!("{}:{}\n", name, location);
+    assert!(stderr.contains(&expected), "expected an error at {}, got:\n{}", location, stderr);
+}
+
+#[test]
+fn missing_semicolon_points_after_the_statement() {
+    assert_reported_at("semi.cx", "fn main() -> int {\n    var x: int = 1\n    x\n}\n", "2:19");
+}
+
+#[test]
+fn only_the_value_of_a_block_may_leave_out_its_semicolon() {
+    let stderr = errors("statements.cx", "fn main() -> int {\n    var x: int = 1;\n    x = 2 x = 3\n    x\n}\n");
+    assert!(stderr.contains("error[E0011]: expected `;`\n"), "got:\n{}", stderr);
+    assert!(stderr.contains("statements.cx:3:10\n"), "got:\n{}", stderr);
+    assert!(stderr.contains("statements.cx:3:16\n"), "got:\n{}", stderr);
+    assert_eq!(stderr.matches("error[").count(), 2, "got:\n{}", stderr);
+}
+
+#[test]
+fn missing_paren_points_after_the_last_argument() {
+    assert_reported_at("call.cx", "fn main() -> int {\n    f(1, 2\n}\n", "2:11");
+}
+
+#[test]
+fn missing_colon_points_after_the_parameter_name() {
+    assert_reported_at("param.cx", "fn f(a int) {}\n", "1:7");
+}
+
+#[test]
+fn missing_identifier_points_at_the_offending_token() {
+    assert_reported_at("name.cx", "\n\nfn 42() {}\n", "3:4");
+}
+
+#[test]
+fn unexpected_token_points_at_the_token() {
+    assert_reported_at("type.cx", "var x: = 1;\n", "1:8");
+}
+
+#[test]
+fn end_of_file_points_at_the_end() {
+    assert_reported_at("eof.cx", "fn main() -> int {\n    1 +\n", "3:1");
+}
+
+#[test]
+fn end_of_file_without_a_line_break_stays_on_the_last_line() {
+    assert_reported_at("eof_no_newline.cx", "fn main() -> int {\n    1 +", "2:8");
+}
+
+#[test]
+fn columns_count_characters_not_bytes() {
+    let stderr = errors("multibyte.cx", "// h\u{e9}llo w\u{f6}rld\nvar s: *char = \"\u{fc}\u{fc}\"; @\n");
+    assert!(stderr.contains("multibyte.cx:2:22\n"), "got:\n{}", stderr);
+    // the underline lines up with the character
+    assert!(stderr.contains("2 | var s: *char = \"\u{fc}\u{fc}\"; @\n  |                      ^\n"), "got:\n{}", stderr);
+}
+
+#[test]
+fn lines_are_found_past_many_lines_and_crlf_breaks() {
+    let mut source = "fn main() -> int {\r\n".to_string();
+    for _ in 0..200 {
+        source.push_str("    0;\r\n");
+    }
+    source.push_str("    1 @ 2\r\n}\r\n");
+    let stderr = errors("many_lines.cx", &source);
+    assert!(stderr.contains("many_lines.cx:202:7\n"), "got:\n{}", stderr);
+    // the carriage return is not part of the shown line
+    assert!(stderr.contains("202 |     1 @ 2\n    |       ^\n"), "got:\n{}", stderr);
+}
+
+#[test]
+fn each_file_resolves_its_own_locations() {
+    let (first, second) = (common::path("first.cx"), common::path("second.cx"));
+    std::fs::write(&first, "fn f() -> int {\n    1\n}\n").unwrap();
+    std::fs::write(&second, "\n\nfn g() -> int { @ }\n").unwrap();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_cx"))
+        .args(["--color=never", "--emit=ir"])
+        .args([&first, &second])
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains(&format!(" --> {}:3:17\n", second.display())), "got:\n{}", stderr);
+    assert!(!stderr.contains(&first.display().to_string()), "got:\n{}", stderr);
+}
+
+#[test]
+fn nested_functions_point_at_the_inner_fn() {
+    assert_reported_at("nested.cx", "fn main() -> int {\n    fn g() -> int { 1 }\n    g()\n}\n", "2:5");
+}
+
+#[test]
+fn top_level_expressions_are_rejected() {
+    assert_reported_at("top_level.cx", "fn main() -> int { 0 }\n\n1 + 2;\n", "3:1");
+}
+
+#[test]
+fn only_functions_can_be_called() {
+    let source = "fn f(n: int) -> int { n }\nfn main() -> int {\n    var x: int = 1;\n    var f: int = 2;\n    x(2) + f(1)\n}\n";
+    let stderr = errors("call.cx", source);
+    assert!(stderr.contains("error[E0021]: `()` cannot be applied to `int`\n"), "got:\n{}", stderr);
+    // the local `f` hides the function
+    assert!(stderr.contains("call.cx:5:5\n") && stderr.contains("call.cx:5:12\n"), "got:\n{}", stderr);
+    assert_eq!(stderr.matches("error[").count(), 2, "got:\n{}", stderr);
+}
+
+#[test]
+fn lexical_errors_are_located() {
+    assert_reported_at("char.cx", "fn main() -> int {\n    1 @ 2\n}\n", "2:7");
+}
+
+#[test]
+fn errors_show_the_underlined_source_line() {
+    let stderr = errors("snippet.cx", "fn main() -> int {\n    var x: int = 300u8;\n}\n");
+    let expected = "\
+error[E0020]: integer literal `300u8` does not fit in `u8`
+ --> ";
+    assert!(stderr.starts_with(expected), "got:\n{}", stderr);
+    let snippet = "\
+  |
+2 |     var x: int = 300u8;
+  |                  ^^^^^
+";
+    assert!(stderr.contains(snippet), "got:\n{}", stderr);
+}
+
+#[test]
+fn colors_can_be_forced() {
+    // the last --color wins over the one the helper passes
+    let output = common::compile("color.cx", "fn main() -> int { 1 @ 2 }\n", &["--emit=ast", "--color=always"]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("\x1b[1;31merror[E0001]"), "got:\n{}", stderr);
+}
@@ -0,0 +1,50 @@
+mod common;
+
+use std::process::Command;
+
+fn explain(code: &str) -> (bool, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_cx")).args(["--explain", code]).output().unwrap();
+    (output.status.success(), String::from_utf8(output.stdout).unwrap())
+}
+
+// the indented code block after "Erroneous code example:"
+fn erroneous_example(text: &str) -> String {
+    let (_, rest) = text.split_once("Erroneous code example:\n\n").unwrap();
+    rest.lines()
+        .take_while(|line| line.is_empty() || line.starts_with("    "))
+        .map(|line| format!("{}\n", line.strip_prefix("    ").unwrap_or(line)))
+        .collect()
+}
+
+fn codes() -> impl Iterator<Item = String> {
+    (1..=30).map(|n| format!("E{:04}", n))
+}
+
+#[test]
+fn every_code_is_explained() {
+    for code in codes() {
+        let (ok, text) = explain(&code);
+        assert!(ok, "{} has no explanation", code);
+        assert!(text.contains("Erroneous code example:"), "{} has no example", code);
+    }
+}
+
+#[test]
+fn unknown_codes_fail() {
+    let (ok, text) = explain("E9999");
+    assert!(!ok);
+    assert!(text.is_empty());
+}
+
+// every example really produces its error
+#[test]
+fn examples_report_their_code() {
+    for code in codes() {
+        let (_, text) = explain(&code);
+        let name = format!("{}.cx", code);
+        let output = common::compile(&name, &erroneous_example(&text), &["--emit=ir", "--error-format=json"]);
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        let first = stderr.lines().next().unwrap_or_default();
+        assert!(first.starts_with(&format!("{{\"code\":\"{}\"", code)), "the example of {} reported:\n{}", code, stderr);
+    }
+}
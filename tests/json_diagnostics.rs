@@ -0,0 +1,25 @@
+mod common;
+
+fn json_errors(name: &str, source: &str) -> Vec<String> {
+    let output = common::compile(name, source, &["--emit=asm", "--error-format=json"]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let path = common::path(name);
+    stderr.lines().map(|line| line.replace(path.to_str().unwrap(), name)).collect()
+}
+
+#[test]
+fn errors_are_one_object_per_line() {
+    let lines = json_errors("escape.cx", "fn main() -> int {\n    \"\\q\";\n    1 @\n}\n");
+    assert_eq!(lines, [
+        r#"{"code":"E0003","severity":"error","lint":null,"message":"invalid escape sequence `\\q`","spans":[{"file":"escape.cx","line_start":2,"column_start":6,"line_end":2,"column_end":8,"byte_start":24,"byte_end":26,"is_primary":true,"label":null}],"notes":[],"suggestions":[]}"#,
+        r#"{"code":"E0001","severity":"error","lint":null,"message":"invalid character '@'","spans":[{"file":"escape.cx","line_start":3,"column_start":7,"line_end":3,"column_end":8,"byte_start":35,"byte_end":36,"is_primary":true,"label":null}],"notes":[],"suggestions":[]}"#,
+    ]);
+}
+
+#[test]
+fn warnings_carry_their_lint_and_suggestions() {
+    let lines = json_errors("lint.cx", "fn main() -> int {\n    var x: int = 1;\n    0\n}\n");
+    assert_eq!(lines, [
+        r#"{"code":null,"severity":"warning","lint":"unused_variables","message":"unused variable `x`","spans":[{"file":"lint.cx","line_start":2,"column_start":9,"line_end":2,"column_end":10,"byte_start":27,"byte_end":28,"is_primary":true,"label":null}],"notes":[],"suggestions":["if this is intentional, prefix it with an underscore: `_x`"]}"#,
+    ]);
+}
@@ -0,0 +1,159 @@
+mod common;
+
+// the tokens of `source`, one per line, which must lex cleanly
+fn tokens(name: &str, source: &str) -> String {
+    let output = common::compile(name, source, &["--emit=tokens"]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success() && stderr.is_empty(), "{} did not lex cleanly:\n{}", name, stderr);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+// what the lexer reported for `source`, which must not lex
+fn lex_errors(name: &str, source: &str) -> String {
+    let output = common::compile(name, source, &["--emit=tokens"]);
+    assert!(!output.status.success(), "{} lexed without errors", name);
+    String::from_utf8(output.stderr).unwrap()
+}
+
+#[test]
+fn strings_decode_their_escapes() {
+    let tokens = tokens("escapes.cx", r#""a\tb\\\"\x41\0\n\r\'""#);
+    assert_eq!(tokens, "string(\"a\\tb\\\\\\\"A\\0\\n\\r'\")\nEOF\n");
+    // the bytes reach the data section as they were decoded
+    let asm = common::asm("escapes_data.cx", "var s: *char = \"a\\tb\\\\\\\"\\x41\\0\";\n");
+    assert!(asm.contains("str_0: db \"a\", 9, \"b\", 92, 34, \"A\", 0, 0\n"), "got:\n{}", asm);
+}
+
+#[test]
+fn bad_escapes_point_at_themselves() {
+    let stderr = lex_errors("bad_escapes.cx", r#""\q" "\x4" "\x""#);
+    assert!(stderr.contains("error[E0003]: invalid escape sequence `\\q`\n --> "), "got:\n{}", stderr);
+    assert!(stderr.contains("bad_escapes.cx:1:2\n"), "got:\n{}", stderr);
+    // `\x` takes exactly two hex digits
+    assert!(stderr.contains("error[E0003]: invalid escape sequence `\\x4`\n"), "got:\n{}", stderr);
+    assert!(stderr.contains("bad_escapes.cx:1:7\n"), "got:\n{}", stderr);
+    assert!(stderr.contains("error[E0003]: invalid escape sequence `\\x`\n"), "got:\n{}", stderr);
+    assert!(stderr.contains("bad_escapes.cx:1:13\n"), "got:\n{}", stderr);
+}
+
+#[test]
+fn strings_end_with_their_line() {
+    let stderr = lex_errors("open_string.cx", "\"open\nx\n");
+    assert!(stderr.contains("error[E0002]: unterminated string literal\n --> "), "got:\n{}", stderr);
+    assert!(stderr.contains("open_string.cx:1:1\n"), "got:\n{}", stderr);
+    assert!(stderr.contains("1 | \"open\n  | ^^^^^\n"), "got:\n{}", stderr);
+}
+
+#[test]
+fn chars_are_single_bytes() {
+    let tokens = tokens("chars.cx", r"'a' '\n' '\'' '\x41' '\0'");
+    assert_eq!(tokens, "char('a')\nchar('\\n')\nchar('\\'')\nchar('A')\nchar('\\0')\nEOF\n");
+}
+
+#[test]
+fn bad_chars_are_reported() {
+    let stderr = lex_errors("bad_chars.cx", "'' 'ab' '\u{e9}' '\\q'\n'1\n'\\n\n");
+    let expected = [
+        ("error[E0005]: empty character literal", "1:1"),
+        ("error[E0006]: character literal holds more than one character", "1:4"),
+        ("error[E0007]: non-ASCII character literal", "1:9"),
+        ("error[E0003]: invalid escape sequence `\\q`", "1:14"),
+        // without a closing quote on the same line
+        ("error[E0004]: unterminated character literal", "2:1"),
+        ("error[E0004]: unterminated character literal", "3:1"),
+    ];
+    let path = common::path("bad_chars.cx");
+    for (message, location) in expected {
+        let reported = format!("{}\n --> {}:{}\n", message, path.display(), location);
+        assert!(stderr.contains(&reported), "no {} at {}, got:\n{}", message, location, stderr);
+    }
+    assert_eq!(stderr.matches("error[").count(), expected.len(), "got:\n{}", stderr);
+}
+
+#[test]
+fn comments_are_skipped() {
+    let source = "a // line /* not a block\nb /* x /* nested */ still comment */ c / d\n\"// kept\" e // at the end";
+    let tokens = tokens("comments.cx", source);
+    assert_eq!(tokens, "identifier(a)\nidentifier(b)\nidentifier(c)\n/\nidentifier(d)\nstring(\"// kept\")\nidentifier(e)\nEOF\n");
+}
+
+#[test]
+fn unterminated_block_comments_point_at_their_opening() {
+    // the outer comment is the one left open
+    let stderr = lex_errors("open_comment.cx", "a\n  /* open /* nested */\nb\n");
+    assert!(stderr.contains("error[E0008]: unterminated block comment\n --> "), "got:\n{}", stderr);
+    assert!(stderr.contains("open_comment.cx:2:3\n"), "got:\n{}", stderr);
+    assert!(stderr.contains("2 |   /* open /* nested */\n  |   ^^\n"), "got:\n{}", stderr);
+}
+
+// every operator and punctuation mark, each displayed the way it is written
+const OPERATORS: &str = "== != < > <= >= = && || ! ++ -- % ^ << >> & | ~ <- -> + - * / \
+    += -= *= /= %= &= |= ^= <<= >>= ( ) { } ; : ,";
+
+#[test]
+fn every_operator_is_lexed() {
+    let tokens = tokens("operators.cx", OPERATORS);
+    let expected: Vec<&str> = OPERATORS.split_whitespace().chain(["EOF"]).collect();
+    assert_eq!(tokens.lines().collect::<Vec<_>>(), expected);
+}
+
+#[test]
+fn operators_are_munched_maximally() {
+    let tokens = tokens("munch.cx", "a>>=b->c&&d<<=e<-f>>g---h=!=i:::j");
+    let expected = [
+        "identifier(a)", ">>=", "identifier(b)", "->", "identifier(c)", "&&", "identifier(d)", "<<=",
+        "identifier(e)", "<-", "identifier(f)", ">>", "identifier(g)", "--", "-", "identifier(h)", "=", "!=",
+        "identifier(i)", "::", ":", "identifier(j)", "EOF",
+    ];
+    assert_eq!(tokens.lines().collect::<Vec<_>>(), expected);
+}
+
+#[test]
+fn keywords_and_literals_display_as_written() {
+    let source = "fn var int char struct enum return if else while for break continue void const static \
+        extern typedef sizeof switch case default fallthrough do goto union i8 i16 i32 i64 u8 u16 u32 u64";
+    let keywords = tokens("keywords.cx", source);
+    let expected: Vec<&str> = source.split_whitespace().chain(["EOF"]).collect();
+    assert_eq!(keywords.lines().collect::<Vec<_>>(), expected);
+
+    let tokens = tokens("literals.cx", "name 42 7u8 'outer \"s\" 'c'");
+    assert_eq!(tokens, "identifier(name)\ninteger(42)\ninteger(7u8)\nlabel('outer)\nstring(\"s\")\nchar('c')\nEOF\n");
+}
+
+#[test]
+fn integers_have_bases_separators_and_suffixes() {
+    let tokens = tokens("numbers.cx", "0x1F 0o17 0b1010 1_000_000 0xff_ffu16 9223372036854775807 18446744073709551615u64");
+    let expected = [
+        "integer(31)", "integer(15)", "integer(10)", "integer(1000000)", "integer(65535u16)",
+        "integer(9223372036854775807)", "integer(18446744073709551615u64)", "EOF",
+    ];
+    assert_eq!(tokens.lines().collect::<Vec<_>>(), expected);
+}
+
+#[test]
+fn malformed_and_overflowing_integers_are_reported() {
+    let stderr = lex_errors("bad_numbers.cx", "0x 0b102 12ab 99999999999999999999 0x1_0000_0000_0000_0000");
+    let expected = [
+        ("0x", "1:1"),
+        // digits outside the base and letters that are not a suffix
+        ("0b102", "1:4"),
+        ("12ab", "1:10"),
+        // too large for 64 bits
+        ("99999999999999999999", "1:15"),
+        ("0x1_0000_0000_0000_0000", "1:36"),
+    ];
+    let path = common::path("bad_numbers.cx");
+    for (number, location) in expected {
+        let reported = format!("error[E0009]: invalid number `{}`\n --> {}:{}\n", number, path.display(), location);
+        assert!(stderr.contains(&reported), "no {} at {}, got:\n{}", number, location, stderr);
+    }
+    assert_eq!(stderr.matches("error[").count(), expected.len(), "got:\n{}", stderr);
+}
+
+#[test]
+fn suffixed_literals_must_fit_their_type() {
+    let stderr = common::errors("fit.cx", "fn main() -> int {\n    var a: u8 = 256u8;\n    var b: i8 = -128i8;\n    a + b\n}\n");
+    assert!(stderr.contains("error[E0020]: integer literal `256u8` does not fit in `u8`\n"), "got:\n{}", stderr);
+    // the negation is part of the literal
+    assert_eq!(stderr.matches("error[").count(), 1, "got:\n{}", stderr);
+}
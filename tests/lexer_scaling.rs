@@ -0,0 +1,77 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+// a function that exercises every kind of token the lexer produces
+const CHUNK: &str = "fn f_%(a: int, b: *char) -> int { // comment\n\
+    var x: u8 = 0x1f_u8; /* block /* nested */ */\n\
+    x <<= 2; b = \"text\\n\"; return a + 'c' * (x >= 3 && !b);\n\
+}\n";
+
+fn input(name: &str, functions: usize) -> PathBuf {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let mut text = String::new();
+    for i in 0..functions {
+        text.push_str(&CHUNK.replace('%', &i.to_string()));
+    }
+    fs::write(&path, text).unwrap();
+    path
+}
+
+fn lex(path: &PathBuf) -> Duration {
+    let out = path.with_extension("tokens");
+    let start = Instant::now();
+    let status = Command::new(env!("CARGO_BIN_EXE_cx"))
+        .arg("--emit=tokens")
+        .arg("-o")
+        .arg(&out)
+        .arg(path)
+        .status()
+        .unwrap();
+    let elapsed = start.elapsed();
+    assert!(status.success());
+    elapsed
+}
+
+// the tokens written for `path`, one per line
+fn tokens(path: &PathBuf) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_cx")).arg("--emit=tokens").arg(path).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn large_inputs_are_lexed_to_the_end() {
+    let per_chunk = tokens(&input("complete_one.cx", 1)).lines().count() - 1;
+    let large = input("complete.cx", 8_000);
+    let tokens = tokens(&large);
+    assert_eq!(tokens.lines().count(), 8_000 * per_chunk + 1);
+    assert!(tokens.ends_with("}\nEOF\n"));
+
+    // offsets stay right to the last byte: a mistake there is found on the last line
+    let mut text = fs::read_to_string(&large).unwrap();
+    text.push_str("  @");
+    fs::write(&large, text).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_cx")).args(["--color=never", "--emit=tokens"]).arg(&large).output().unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains(&format!("{}:{}:3\n", large.display(), 4 * 8_000 + 1)), "got:\n{}", stderr);
+}
+
+// quadrupling the input must not take anywhere near 16 times as long.
+// wall-clock timing depends on the machine, so this only runs on request:
+// cargo test --test lexer_scaling -- --ignored
+#[test]
+#[ignore = "benchmark"]
+fn lexing_scales_linearly() {
+    let small = input("scaling_small.cx", 2_000);
+    let large = input("scaling_large.cx", 8_000);
+
+    // best of a few runs, to keep a busy machine from skewing the ratio
+    let best = |path: &PathBuf| (0..3).map(|_| lex(path)).min().unwrap();
+    let small = best(&small).max(Duration::from_millis(1));
+    let large = best(&large);
+
+    let ratio = large.as_secs_f64() / small.as_secs_f64();
+    assert!(ratio < 10.0, "lexing 4x the input took {:.1}x as long ({:?} vs {:?})", ratio, large, small);
+}
@@ -0,0 +1,34 @@
+mod common;
+
+use std::process::Output;
+
+const UNUSED: &str = "fn main() -> int {\n    var x: int = 1;\n    var _y: int = 2;\n    0\n}\n";
+
+fn compile(name: &str, flags: &[&str]) -> Output {
+    common::compile(name, UNUSED, &[&["--emit=asm"], flags].concat())
+}
+
+#[test]
+fn unused_variables_warn_without_failing() {
+    let output = compile("unused.cx", &[]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "got:\n{}", stderr);
+    assert!(stderr.starts_with("warning: unused variable `x`\n"), "got:\n{}", stderr);
+    assert!(stderr.contains("= help: if this is intentional, prefix it with an underscore: `_x`"), "got:\n{}", stderr);
+    assert!(!stderr.contains("`_y`"), "got:\n{}", stderr);
+}
+
+#[test]
+fn werror_turns_warnings_into_errors() {
+    let output = compile("werror.cx", &["-Werror"]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(!output.status.success());
+    assert!(stderr.starts_with("error: unused variable `x`\n"), "got:\n{}", stderr);
+}
+
+#[test]
+fn allowed_lints_are_silent() {
+    let output = compile("allow.cx", &["-A", "unused_variables", "-Werror"]);
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty(), "got:\n{}", String::from_utf8_lossy(&output.stderr));
+}
@@ -0,0 +1,50 @@
+mod common;
+
+const SOURCE: &str = "\
+fn add(a int, b: int, c: ) -> int {
+    var x: int = 1 +;
+    var y: int = f(1, , 3);
+    g(1 2);
+    x + y
+}
+
+fn second(a: int -> int {
+    (1 + ;
+    var ok: int = 2;
+    ok
+}
+
+var z: = 4;
+
+fn third() -> int {
+    h(1, 2
+}
+
+fn fourth() -> int {
+    {
+        1 +
+";
+
+// every mistake is reported once, at its own location
+#[test]
+fn one_file_reports_every_mistake() {
+    let stderr = common::errors("recovery.cx", SOURCE);
+    let reported: Vec<(&str, &str)> = stderr
+        .lines()
+        .filter_map(|line| line.strip_prefix("error["))
+        .zip(stderr.lines().filter_map(|line| line.rsplit_once("recovery.cx:").map(|(_, loc)| loc)))
+        .map(|(title, loc)| (&title[..5], loc))
+        .collect();
+    assert_eq!(reported, [
+        ("E0011", "1:9"),
+        ("E0010", "1:26"),
+        ("E0010", "2:21"),
+        ("E0010", "3:23"),
+        ("E0011", "4:8"),
+        ("E0011", "8:17"),
+        ("E0010", "9:10"),
+        ("E0010", "14:8"),
+        ("E0011", "17:11"),
+        ("E0014", "23:1"),
+    ]);
+}
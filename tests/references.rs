@@ -0,0 +1,45 @@
+mod common;
+
+use common::{asm, errors};
+
+#[test]
+fn references_are_read_through_like_pointers() {
+    let source = "fn read(p: &u8) -> u8 { *p }\n\nfn main() -> int {\n    var n: u8 = 200u8;\n    var r: &u8 = &n;\n    read(r) + read(&n)\n}\n";
+    let asm = asm("read.cx", source);
+    let read = &asm[asm.find("read:\n").unwrap()..];
+    assert!(read.contains("movzx eax, byte [rax]\n"), "got:\n{}", asm);
+}
+
+#[test]
+fn references_are_not_written_through() {
+    let source = "fn bump(counter: &int) {\n    *counter += 1;\n    *counter = 0;\n}\n";
+    let stderr = errors("write.cx", source);
+    assert!(stderr.contains("error[E0019]: expected a mutable reference but found an immutable one\n"), "got:\n{}", stderr);
+    assert!(stderr.contains("write.cx:2:5\n") && stderr.contains("write.cx:3:5\n"), "got:\n{}", stderr);
+    assert!(stderr.contains("= note: `&int` is only read through, write through a `*int`\n"), "got:\n{}", stderr);
+    assert_eq!(stderr.matches("error[").count(), 2, "got:\n{}", stderr);
+}
+
+#[test]
+fn references_do_not_become_pointers() {
+    let source = "\
+fn store(p: *int) -> *int { p }
+
+fn main() -> int {
+    var n: int = 1;
+    var r: &int = &n;
+    var p: *int = r;
+    p = r;
+    store(r);
+    0
+}
+
+fn back(r: &int) -> *int { r }
+";
+    let stderr = errors("convert.cx", source);
+    for location in ["6:19", "7:9", "8:11", "12:28"] {
+        assert!(stderr.contains(&format!("convert.cx:{}\n", location)), "expected an error at {}, got:\n{}", location, stderr);
+    }
+    assert_eq!(stderr.matches("error[E0019]").count(), 4, "got:\n{}", stderr);
+    assert_eq!(stderr.matches("error[").count(), 4, "got:\n{}", stderr);
+}